@@ -1,658 +1,7333 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use directories::ProjectDirs;
+use base64::Engine;
+use directories::{ProjectDirs, UserDirs};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+#[cfg(target_os = "macos")]
+use objc2::rc::{Allocated, Retained};
+#[cfg(target_os = "macos")]
+use objc2::runtime::{AnyClass, AnyObject, ClassBuilder, Imp, Sel};
+#[cfg(target_os = "macos")]
+use objc2::{class, msg_send, sel};
+#[cfg(target_os = "macos")]
+use objc2_foundation::NSString;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::sync::RwLock;
-#[cfg(target_os = "macos")]
-use std::time::Duration;
+use std::str::FromStr;
+use std::sync::{mpsc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::async_runtime::JoinHandle;
 use tauri::image::Image;
 use tauri::menu::{
-    CheckMenuItem, Menu, MenuBuilder, MenuItem, PredefinedMenuItem, SubmenuBuilder, HELP_SUBMENU_ID,
+    CheckMenuItem, IsMenuItem, Menu, MenuBuilder, MenuItem, PredefinedMenuItem, Submenu,
+    SubmenuBuilder, HELP_SUBMENU_ID, WINDOW_SUBMENU_ID,
 };
-use tauri::tray::{TrayIconBuilder, TrayIconEvent};
-#[cfg(target_os = "macos")]
-use tauri::WebviewWindow;
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::window::Color;
 use tauri::Wry;
 use tauri::{
-    webview::PageLoadPayload, AppHandle, Manager, Webview, WebviewUrl, WebviewWindowBuilder,
+    webview::{DownloadEvent, PageLoadEvent, PageLoadPayload},
+    AppHandle, DragDropEvent, Emitter, LogicalPosition, LogicalSize, Manager, PhysicalPosition,
+    RunEvent, Theme, Webview, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent,
 };
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut};
+use tauri_plugin_autostart::{MacosLauncher, ManagerExt as AutostartExt};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_deep_link::DeepLinkExt;
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
+use tauri_plugin_notification::NotificationExt;
+use tauri_plugin_updater::UpdaterExt;
+use tauri_plugin_window_state::{AppHandleExt, StateFlags};
 use url::Url;
-#[cfg(target_os = "macos")]
 use tokio::time::sleep;
+use tracing::{error, info, warn};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::reload;
 #[cfg(target_os = "macos")]
-use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
+use window_vibrancy::{apply_vibrancy, clear_vibrancy, NSVisualEffectMaterial};
 
 // ============================================================================
 // Configuration
 // ============================================================================
 
-const DEFAULT_SERVER_URL: &str = "https://cloud.onyx.app";
+const ONYX_CLOUD_URL: &str = "https://cloud.onyx.app";
+// Lets distributors ship builds pointed at their own instance
+const BUILD_DEFAULT_SERVER_URL: Option<&str> = option_env!("ONYX_DEFAULT_SERVER_URL");
 const CONFIG_FILE_NAME: &str = "config.json";
+const FALLBACK_CONFIG_DIR_NAME: &str = "onyx-desktop-config";
+const RECENT_CHATS_FILE_NAME: &str = "recent_chats.json";
+const WINDOW_SESSION_FILE_NAME: &str = "window_session.json";
+const CUSTOM_CSS_FILE_NAME: &str = "custom.css";
+const LOG_DIR_NAME: &str = "logs";
+const LOG_FILE_PREFIX: &str = "onyx";
+const MAX_LOG_FILES: usize = 7;
+const CUSTOM_CSS_STYLE_ID: &str = "onyx-desktop-custom-css";
+const MAX_RECENT_CHATS: usize = 8;
+const MAX_RECENT_SERVERS: usize = 5;
+const CONFIG_VERSION: u32 = 2;
+const SERVER_URL_ENV_VAR: &str = "ONYX_SERVER_URL";
+const HEALTH_CHECK_PATH: &str = "/api/health";
+const LOGIN_PATH: &str = "/auth/login";
+// Onyx's OAuth, OIDC, and SAML callbacks; `sso_callback_path` adds to these
+const SSO_CALLBACK_PATHS: [&str; 3] = [
+    "/auth/oauth/callback",
+    "/auth/oidc/callback",
+    "/auth/saml/callback",
+];
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+const MIN_CONNECTION_CHECK_INTERVAL_SECS: u64 = 5;
+const KEEP_ALIVE_PATH: &str = "/api/me";
+const MIN_KEEP_ALIVE_MINUTES: u64 = 1;
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MIN_AUTO_RELOAD_MINUTES: u64 = 1;
+// An auto-reload waits until there's been no input for this long, so it
+// doesn't wipe out something being typed
+const AUTO_RELOAD_QUIET_SECS: u64 = 30;
+const MIN_IDLE_THRESHOLD_SECS: u64 = 10;
+const CONFIG_RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+// Quitting waits at most this long for state to be written to disk
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+// Config fields kept in the OS keychain rather than in config.json
+const SECRET_CONFIG_FIELDS: &[&str] = &["basic_auth_pass"];
+const KEYCHAIN_SERVICE: &str = "app.onyx.desktop";
 #[cfg(target_os = "macos")]
 const TITLEBAR_SCRIPT: &str = include_str!("../../src/titlebar.js");
+const FIND_SCRIPT: &str = include_str!("../../src/find.js");
+const NAVIGATION_SCRIPT: &str = include_str!("../../src/navigation.js");
+const PAGE_TITLE_SCRIPT: &str = include_str!("../../src/pagetitle.js");
+const DEEP_LINK_SCHEME: &str = "onyx";
+const CONFIG_CHANGED_EVENT: &str = "config-changed";
+const THEME_CHANGED_EVENT: &str = "theme-changed";
+const DOWNLOAD_STARTED_EVENT: &str = "download-started";
+const DOWNLOAD_FINISHED_EVENT: &str = "download-finished";
+const USER_IDLE_EVENT: &str = "user-idle";
+const USER_ACTIVE_EVENT: &str = "user-active";
+const FILES_DROPPED_EVENT: &str = "files-dropped";
+// Dropped files up to this size are also sent inline as data URLs
+const MAX_INLINE_DROP_BYTES: u64 = 10 * 1024 * 1024;
+// Set at build time for builds that ship updates; unset builds can't update
+const UPDATER_PUBKEY: Option<&str> = option_env!("ONYX_UPDATER_PUBKEY");
+const UPDATER_ENDPOINT: Option<&str> = option_env!("ONYX_UPDATER_ENDPOINT");
+const SETTINGS_WINDOW_LABEL: &str = "settings";
+const SETTINGS_PAGE: &str = "settings.html";
+const OFFLINE_PAGE: &str = "offline.html";
+const QUICK_CHAT_WINDOW_LABEL: &str = "quick-chat";
+const QUICK_CHAT_WINDOW_WIDTH: f64 = 640.0;
+const QUICK_CHAT_WINDOW_HEIGHT: f64 = 480.0;
+const DEFAULT_WINDOW_WIDTH: f64 = 1200.0;
+const DEFAULT_WINDOW_HEIGHT: f64 = 800.0;
+const MIN_WINDOW_WIDTH: f64 = 800.0;
+const MIN_WINDOW_HEIGHT: f64 = 600.0;
+const WINDOW_CASCADE_STEP: f64 = 30.0;
+const MIN_VISIBLE_WINDOW_PART: f64 = 50.0;
+const COMPACT_WINDOW_WIDTH: f64 = 380.0;
+const COMPACT_WINDOW_HEIGHT: f64 = 600.0;
+// Gap between a compact window and the edges of the screen
+const COMPACT_WINDOW_MARGIN: f64 = 16.0;
+const DEFAULT_ZOOM_LEVEL: f64 = 1.0;
+const DEFAULT_BACKGROUND_COLOR: Color = Color(0x1a, 0x1a, 0x2e, 0xff);
+const LIGHT_BACKGROUND_COLOR: Color = Color(0xf5, 0xf5, 0xf5, 0xff);
+const MIN_ZOOM_LEVEL: f64 = 0.25;
+const MAX_ZOOM_LEVEL: f64 = 5.0;
+const ZOOM_STEP: f64 = 0.1;
+const ALWAYS_ON_TOP_MENU_ID: &str = "toggle_always_on_top";
+const COMPACT_MENU_ID: &str = "toggle_compact";
+const SPELLCHECK_MENU_ID: &str = "toggle_spellcheck";
 const TRAY_ID: &str = "onyx-tray";
 const TRAY_ICON_BYTES: &[u8] = include_bytes!("../icons/tray-icon.png");
+const TRAY_ICON_UNREAD_BYTES: &[u8] = include_bytes!("../icons/tray-icon-unread.png");
 const TRAY_MENU_OPEN_APP_ID: &str = "tray_open_app";
 const TRAY_MENU_OPEN_CHAT_ID: &str = "tray_open_chat";
 const TRAY_MENU_SHOW_IN_BAR_ID: &str = "tray_show_in_menu_bar";
+const TRAY_MENU_CONNECTION_INFO_ID: &str = "tray_connection_info";
+const TRAY_MENU_MINIMIZE_ALL_ID: &str = "tray_minimize_all";
+const TRAY_MENU_SHOW_ALL_ID: &str = "tray_show_all";
+const TRAY_MENU_RESTART_ID: &str = "tray_restart";
 const TRAY_MENU_QUIT_ID: &str = "tray_quit";
+const QUIT_MENU_ID: &str = "quit_app";
+const TRAY_MENU_RECENT_CHAT_PREFIX: &str = "tray_recent_chat:";
+const TRAY_MENU_SERVER_PREFIX: &str = "tray_server:";
+const TRAY_MENU_NEW_WINDOW_SERVER_PREFIX: &str = "tray_new_window_server:";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AppConfig {
-    /// The Onyx server URL (default: https://cloud.onyx.app)
-    pub server_url: String,
+/// Default and minimum sizes for new windows, in logical pixels
+#[derive(Debug, Clone, Copy)]
+pub struct WindowSizes {
+    pub width: f64,
+    pub height: f64,
+    pub min_width: f64,
+    pub min_height: f64,
+}
 
-    /// Optional: Custom window title
-    #[serde(default = "default_window_title")]
-    pub window_title: String,
+/// A window's outer position and inner size, in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
 }
 
-fn default_window_title() -> String {
-    "Onyx".to_string()
+/// A named Onyx server the user can switch to from the tray
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServerProfile {
+    pub name: String,
+    pub url: String,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
+impl ServerProfile {
+    /// A profile named after the URL's host
+    fn from_url(url: &str) -> Self {
+        let name = Url::parse(url)
+            .ok()
+            .and_then(|parsed| parsed.host_str().map(str::to_string))
+            .unwrap_or_else(|| url.to_string());
         Self {
-            server_url: DEFAULT_SERVER_URL.to_string(),
-            window_title: default_window_title(),
+            name,
+            url: url.to_string(),
         }
     }
 }
 
-/// Get the config directory path
-fn get_config_dir() -> Option<PathBuf> {
-    ProjectDirs::from("app", "onyx", "onyx-desktop").map(|dirs| dirs.config_dir().to_path_buf())
-}
-
-/// Get the full config file path
-fn get_config_path() -> Option<PathBuf> {
-    get_config_dir().map(|dir| dir.join(CONFIG_FILE_NAME))
+/// Window appearance: follow the OS, or force light or dark
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemePreference {
+    #[default]
+    System,
+    Light,
+    Dark,
 }
 
-/// Load config from file, or create default if it doesn't exist
-fn load_config() -> (AppConfig, bool) {
-    let config_path = match get_config_path() {
-        Some(path) => path,
-        None => {
-            return (AppConfig::default(), false);
+impl ThemePreference {
+    /// The theme to force on windows, or `None` to follow the OS
+    fn forced(self) -> Option<Theme> {
+        match self {
+            ThemePreference::System => None,
+            ThemePreference::Light => Some(Theme::Light),
+            ThemePreference::Dark => Some(Theme::Dark),
         }
-    };
-
-    if !config_path.exists() {
-        return (AppConfig::default(), false);
     }
+}
 
-    match fs::read_to_string(&config_path) {
-        Ok(contents) => match serde_json::from_str(&contents) {
-            Ok(config) => (config, true),
-            Err(_) => (AppConfig::default(), false),
-        },
-        Err(_) => (AppConfig::default(), false),
+/// What left-clicking the tray icon does; right-click always opens the menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrayLeftClick {
+    /// Bring the app forward
+    Show,
+    /// Open the tray menu
+    Menu,
+}
+
+impl Default for TrayLeftClick {
+    /// Menu bar icons open their menu on macOS; tray icons open the app elsewhere
+    fn default() -> Self {
+        if cfg!(target_os = "macos") {
+            TrayLeftClick::Menu
+        } else {
+            TrayLeftClick::Show
+        }
     }
 }
 
-/// Save config to file
-fn save_config(config: &AppConfig) -> Result<(), String> {
-    let config_dir = get_config_dir().ok_or("Could not determine config directory")?;
-    let config_path = config_dir.join(CONFIG_FILE_NAME);
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppConfig {
+    /// Config schema version; files written before versioning deserialize as 0
+    #[serde(default)]
+    pub version: u32,
 
-    // Ensure config directory exists
-    fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
+    /// The Onyx server URL (default: https://cloud.onyx.app). Always the URL
+    /// of the active entry in `servers`.
+    pub server_url: String,
 
-    let json = serde_json::to_string_pretty(config)
-        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    /// Optional: Servers to switch between from the tray. Created from
+    /// `server_url` when missing.
+    #[serde(default)]
+    pub servers: Vec<ServerProfile>,
 
-    fs::write(&config_path, json).map_err(|e| format!("Failed to write config: {}", e))?;
+    /// Optional: Index into `servers` of the server in use (default: 0). The
+    /// profile commands take names and keep this index in step.
+    #[serde(default)]
+    pub active_server: usize,
 
-    Ok(())
-}
+    /// Optional: Server URLs most recently set from Settings, newest first
+    /// (at most 5)
+    #[serde(default)]
+    pub recent_servers: Vec<String>,
 
-// Global config state
-struct ConfigState {
-    config: RwLock<AppConfig>,
-    config_initialized: RwLock<bool>,
-    app_base_url: RwLock<Option<Url>>,
-}
+    /// Optional: Window theme, `system`, `light`, or `dark` (default: `system`)
+    #[serde(default)]
+    pub theme: ThemePreference,
 
-fn focus_main_window(app: &AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.unminimize();
-        let _ = window.show();
-        let _ = window.set_focus();
-    } else {
-        trigger_new_window(app);
-    }
-}
+    /// Optional: Custom window title
+    #[serde(default = "default_window_title")]
+    pub window_title: String,
 
-fn trigger_new_chat(app: &AppHandle) {
-    let state = app.state::<ConfigState>();
-    let server_url = state.config.read().unwrap().server_url.clone();
+    /// Optional: Show each window's page title ahead of `window_title`, e.g.
+    /// "Support chat — Onyx" (default: false)
+    #[serde(default)]
+    pub title_follows_page: bool,
 
-    if let Some(window) = app.get_webview_window("main") {
-        let url = format!("{}/chat", server_url);
-        let _ = window.eval(&format!("window.location.href = '{}'", url));
-    }
-}
+    /// Optional: Shortcut overrides, mapping an action name (`new_chat`,
+    /// `reload`, `hard_reload`, `back`, `forward`, `new_window`, `show_app`,
+    /// `open_settings`, `zoom_in`, `zoom_out`, `reset_zoom`, `find`,
+    /// `open_in_browser`, `quick_chat`, `always_on_top`, `go_home`, `print`,
+    /// `screenshot`, `compact_mode`, `minimize_all`, `show_all`, `devtools`,
+    /// `exit_kiosk`) to an accelerator string such as `CmdOrCtrl+T`
+    #[serde(default)]
+    pub shortcuts: HashMap<String, String>,
 
-fn trigger_new_window(app: &AppHandle) {
-    let state = app.state::<ConfigState>();
-    let server_url = state.config.read().unwrap().server_url.clone();
-    let handle = app.clone();
+    /// Optional: Page zoom per server URL (default: 1.0)
+    #[serde(default)]
+    pub zoom_levels: HashMap<String, f64>,
 
-    tauri::async_runtime::spawn(async move {
-        let window_label = format!("onyx-{}", uuid::Uuid::new_v4());
-        let builder = WebviewWindowBuilder::new(
-            &handle,
-            &window_label,
-            WebviewUrl::External(server_url.parse().unwrap()),
-        )
-        .title("Onyx")
-        .inner_size(1200.0, 800.0)
-        .min_inner_size(800.0, 600.0)
-        .transparent(true);
+    /// Optional: Last window size and position per server profile name, so
+    /// each profile reopens with its own layout. Updated as windows close.
+    #[serde(default)]
+    pub window_geometry: HashMap<String, WindowGeometry>,
 
-        #[cfg(target_os = "macos")]
-        let builder = builder
-            .title_bar_style(tauri::TitleBarStyle::Overlay)
-            .hidden_title(true);
+    /// Optional: Hide the main window instead of closing it (default: false).
+    /// On Windows and Linux the app keeps running in the system tray; on macOS
+    /// it stays in the Dock and menu bar. Reopen it from the tray "Open Onyx"
+    /// item or the show-app shortcut; quit from the tray or with Cmd/Ctrl+Q.
+    #[serde(default)]
+    pub close_to_tray: bool,
 
-        #[cfg(target_os = "linux")]
-        let builder = builder.background_color(tauri::window::Color(0x1a, 0x1a, 0x2e, 0xff));
+    /// Optional: Make the show-app shortcut hide the app window when it's
+    /// already in front, instead of always showing it (default: true)
+    #[serde(default = "default_toggle_on_summon")]
+    pub toggle_on_summon: bool,
 
-        if let Ok(window) = builder.build() {
-            #[cfg(target_os = "macos")]
-            {
-                let _ = apply_vibrancy(&window, NSVisualEffectMaterial::Sidebar, None, None);
-                inject_titlebar(window.clone());
-            }
+    /// Optional: Keep app windows above other apps (default: false). Set by
+    /// the last Always on Top choice and applied to every new window.
+    #[serde(default)]
+    pub always_on_top: bool,
 
-            let _ = window.set_focus();
-        }
-    });
+    /// Optional: Check spelling in text fields as you type (default: true)
+    #[serde(default = "default_spellcheck")]
+    pub spellcheck: bool,
+
+    /// Optional: Show the Onyx icon in the menu bar / system tray (default: true)
+    #[serde(default = "default_show_tray_icon")]
+    pub show_tray_icon: bool,
+
+    /// Optional: Reopen the windows from the last run, with their pages, at
+    /// startup (default: true)
+    #[serde(default = "default_restore_session")]
+    pub restore_session: bool,
+
+    /// Optional: Left-clicking the tray icon shows the app (`show`) or opens
+    /// its menu (`menu`) (default: `menu` on macOS, `show` elsewhere)
+    #[serde(default)]
+    pub tray_left_click: TrayLeftClick,
+
+    /// Optional: Ask before quitting while more than one window is open
+    /// (default: false)
+    #[serde(default)]
+    pub confirm_quit: bool,
+
+    /// Optional: Let pages handle file drops with HTML5 drag and drop instead
+    /// of receiving `files-dropped` events (default: false). Applies to
+    /// windows opened after the change.
+    #[serde(default)]
+    pub native_drag_drop: bool,
+
+    /// Optional: Start Onyx automatically when the user logs in (default: false)
+    #[serde(default)]
+    pub launch_at_login: bool,
+
+    /// Optional: Seconds between tray connection-status checks (default: 30, min: 5)
+    #[serde(default = "default_connection_check_interval_secs")]
+    pub connection_check_interval_secs: u64,
+
+    /// Optional: Minutes between requests that keep the web session from
+    /// timing out while the app sits idle (default: off, min: 1)
+    #[serde(default)]
+    pub keep_alive_minutes: Option<u64>,
+
+    /// Optional: Minutes between reloads of the main window, for wall
+    /// displays that should stay current (default: off, min: 1)
+    #[serde(default)]
+    pub auto_reload_minutes: Option<u64>,
+
+    /// Optional: Seconds without keyboard or mouse input before the web app
+    /// is told the user is idle (default: 300, min: 10)
+    #[serde(default = "default_idle_threshold_seconds")]
+    pub idle_threshold_seconds: u64,
+
+    /// Optional: Size of new windows in logical pixels (default: 1200 x 800)
+    #[serde(default = "default_window_width")]
+    pub default_width: f64,
+    #[serde(default = "default_window_height")]
+    pub default_height: f64,
+
+    /// Optional: Smallest size windows can be resized to, no larger than the
+    /// default size (default: 800 x 600)
+    #[serde(default = "default_min_window_width")]
+    pub min_width: f64,
+    #[serde(default = "default_min_window_height")]
+    pub min_height: f64,
+
+    /// Optional: Window background color behind the web app on Windows and
+    /// Linux, as `#RRGGBB` or `#RRGGBBAA` (default: follows the theme)
+    #[serde(default)]
+    pub background_color: Option<String>,
+
+    /// Optional: macOS vibrancy material behind the web app, e.g. `"sidebar"`,
+    /// `"hud"`, or `"fullscreen-ui"`; `"none"` makes windows fully opaque
+    /// (default: follows the theme)
+    #[serde(default)]
+    pub vibrancy: Option<String>,
+
+    /// Optional: Custom User-Agent for the webview (default: the platform's).
+    /// Applies to windows opened after the change.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+
+    /// Optional: HTTP or SOCKS5 proxy for the webview and connection checks,
+    /// e.g. `http://proxy.corp:8080` (default: the system proxy). Applies to
+    /// windows opened after the change.
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+
+    /// Optional: Path to a PEM file of extra CA certificates to trust for
+    /// connection checks, for servers behind an internal CA. The webview uses
+    /// the OS trust store, so install the same CA there as well.
+    #[serde(default)]
+    pub custom_ca_path: Option<String>,
+
+    /// Optional: Accept invalid TLS certificates (default: false).
+    ///
+    /// DANGEROUS: this disables certificate verification, so anyone on the
+    /// network path can impersonate the server and read your session. Only
+    /// enable it for a trusted self-hosted server with a self-signed
+    /// certificate, and prefer `custom_ca_path` whenever possible. Applies to
+    /// connection checks everywhere and to the webview on Linux.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+
+    /// Optional: HTTP basic auth credentials for a saved server that sits
+    /// behind an authenticating reverse proxy (default: none). They're sent
+    /// with connection checks and given to the webview, but only for
+    /// `server_url`. The password is kept in the OS keychain, not this file,
+    /// and is left out whenever the config is serialized for webviews or
+    /// export (see `config_value_with_secrets`).
+    #[serde(default)]
+    pub basic_auth_user: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub basic_auth_pass: Option<String>,
+
+    /// Optional: Check for updates in the background at startup and offer any
+    /// new version in a dialog (default: false)
+    #[serde(default)]
+    pub auto_check_updates: bool,
+
+    /// Optional: Server path the identity provider redirects back to after
+    /// SSO sign-in (default: `/auth/oauth/callback`). `onyx://auth/callback`
+    /// links are forwarded here.
+    #[serde(default = "default_sso_callback_path")]
+    pub sso_callback_path: String,
+
+    /// Optional: Minimum level written to the log file: `error`, `warn`,
+    /// `info`, `debug`, or `trace` (default: `info`)
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+
+    /// Optional: Enable the web inspector via `toggle_devtools` and
+    /// Cmd/Ctrl+Alt+I (default: false; always on in debug builds).
+    ///
+    /// The inspector shows page internals such as cookies, local storage, and
+    /// network traffic including auth headers, so only enable it while
+    /// debugging. Applies after a restart.
+    #[serde(default)]
+    pub developer_mode: bool,
+
+    /// Optional: Lock the app down for public terminals: the main window
+    /// opens fullscreen with no menu bar, no Quit in the tray, no developer
+    /// tools, and only a safe subset of shortcuts (default: false). Applies
+    /// after a restart.
+    #[serde(default)]
+    pub kiosk: bool,
+
+    /// Session-only server URL from `ONYX_SERVER_URL`; never written to disk
+    #[serde(skip)]
+    pub server_url_override: Option<String>,
 }
 
-fn open_docs() {
-    let url = "https://docs.onyx.app";
-    #[cfg(target_os = "macos")]
-    {
-        let _ = Command::new("open").arg(url).status();
-    }
-    #[cfg(target_os = "linux")]
-    {
-        let _ = Command::new("xdg-open").arg(url).status();
+impl AppConfig {
+    /// The server URL to connect to, preferring the session override
+    pub fn effective_server_url(&self) -> &str {
+        self.server_url_override
+            .as_deref()
+            .unwrap_or(&self.server_url)
     }
-    #[cfg(target_os = "windows")]
-    {
-        let _ = Command::new("rundll32")
-            .arg("url.dll,FileProtocolHandler")
-            .arg(url)
-            .status();
+
+    /// Whether `other` differs in a setting that only applies cleanly after a
+    /// restart (User-Agent, proxy, TLS trust, developer mode, and kiosk mode)
+    pub fn needs_restart_from(&self, other: &AppConfig) -> bool {
+        self.user_agent != other.user_agent
+            || self.proxy_url != other.proxy_url
+            || self.custom_ca_path != other.custom_ca_path
+            || self.danger_accept_invalid_certs != other.danger_accept_invalid_certs
+            || self.developer_mode != other.developer_mode
+            || self.kiosk != other.kiosk
     }
-}
 
-fn open_settings(app: &AppHandle) {
-    // Navigate main window to the settings page (index.html) with settings flag
-    let state = app.state::<ConfigState>();
-    let settings_url = state
-        .app_base_url
-        .read()
-        .unwrap()
-        .as_ref()
-        .cloned()
-        .and_then(|mut url| {
-            url.set_query(None);
-            url.set_fragment(Some("settings"));
-            url.set_path("/");
-            Some(url)
+    /// Basic auth credentials to send to `server_url`, if it's the saved
+    /// server. Other profiles and session overrides never receive them.
+    pub fn basic_auth_for(&self, server_url: &str) -> Option<BasicAuth> {
+        let user = self
+            .basic_auth_user
+            .as_deref()
+            .filter(|user| !user.is_empty())?;
+        (server_url == self.server_url).then(|| BasicAuth {
+            user: user.to_string(),
+            pass: self.basic_auth_pass.clone().unwrap_or_default(),
         })
-        .or_else(|| Url::parse("tauri://localhost/#settings").ok());
+    }
 
-    if let Some(window) = app.get_webview_window("main") {
-        if let Some(url) = settings_url {
-            let _ = window.navigate(url);
+    /// Whether the web inspector may be opened
+    pub fn devtools_enabled(&self) -> bool {
+        (cfg!(debug_assertions) || self.developer_mode) && !self.kiosk_enabled()
+    }
+
+    /// Whether kiosk mode is on, from config or `--kiosk`
+    pub fn kiosk_enabled(&self) -> bool {
+        self.kiosk || KIOSK_ARG.get().copied().unwrap_or(false)
+    }
+
+    /// The configured proxy, if set and valid
+    pub fn proxy_url(&self) -> Option<Url> {
+        let raw = self.proxy_url.as_deref()?;
+        match parse_proxy_url(raw) {
+            Ok(url) => Some(url),
+            Err(e) => {
+                warn!("{}; using the system proxy", e);
+                None
+            }
         }
     }
-}
 
-// ============================================================================
-// Tauri Commands
-// ============================================================================
+    /// The saved zoom level for the current server
+    pub fn zoom_level(&self) -> f64 {
+        self.zoom_level_for(self.effective_server_url())
+    }
 
-/// Get the current server URL
-#[tauri::command]
-fn get_server_url(state: tauri::State<ConfigState>) -> String {
-    state.config.read().unwrap().server_url.clone()
-}
+    /// The saved zoom level for `server_url`
+    pub fn zoom_level_for(&self, server_url: &str) -> f64 {
+        self.zoom_levels
+            .get(server_url)
+            .copied()
+            .unwrap_or(DEFAULT_ZOOM_LEVEL)
+    }
 
-#[derive(Serialize)]
-struct BootstrapState {
-    server_url: String,
-    config_exists: bool,
-}
+    /// The configured window sizes, with invalid values replaced and each
+    /// minimum clamped to its default
+    pub fn window_sizes(&self) -> WindowSizes {
+        let positive = |value: f64, name: &str, fallback: f64| {
+            if value.is_finite() && value > 0.0 {
+                value
+            } else {
+                warn!("Invalid {} {}; using {}", name, value, fallback);
+                fallback
+            }
+        };
+        let width = positive(self.default_width, "default_width", DEFAULT_WINDOW_WIDTH);
+        let height = positive(self.default_height, "default_height", DEFAULT_WINDOW_HEIGHT);
+        let mut min_width = positive(self.min_width, "min_width", MIN_WINDOW_WIDTH);
+        let mut min_height = positive(self.min_height, "min_height", MIN_WINDOW_HEIGHT);
 
-/// Get the server URL plus whether a config file exists
-#[tauri::command]
-fn get_bootstrap_state(state: tauri::State<ConfigState>) -> BootstrapState {
-    let server_url = state.config.read().unwrap().server_url.clone();
-    let config_initialized = *state.config_initialized.read().unwrap();
-    let config_exists = config_initialized
-        && get_config_path().map(|path| path.exists()).unwrap_or(false);
+        if min_width > width {
+            warn!(
+                "min_width {} exceeds default_width {}; clamping",
+                min_width, width
+            );
+            min_width = width;
+        }
+        if min_height > height {
+            warn!(
+                "min_height {} exceeds default_height {}; clamping",
+                min_height, height
+            );
+            min_height = height;
+        }
 
-    BootstrapState {
-        server_url,
-        config_exists,
+        WindowSizes {
+            width,
+            height,
+            min_width,
+            min_height,
+        }
     }
-}
 
-/// Set a new server URL and save to config
-#[tauri::command]
-fn set_server_url(state: tauri::State<ConfigState>, url: String) -> Result<String, String> {
-    // Validate URL
-    if !url.starts_with("http://") && !url.starts_with("https://") {
-        return Err("URL must start with http:// or https://".to_string());
+    /// Make the profile at `index` the active server
+    pub fn activate_server(&mut self, index: usize) -> Result<(), String> {
+        let profile = self
+            .servers
+            .get(index)
+            .ok_or_else(|| format!("No server profile at index {}", index))?;
+        self.server_url = validate_server_url(&profile.url)?;
+        self.active_server = index;
+        Ok(())
     }
 
-    let mut config = state.config.write().unwrap();
-    config.server_url = url.trim_end_matches('/').to_string();
-    save_config(&config)?;
-    *state.config_initialized.write().unwrap() = true;
-
-    Ok(config.server_url.clone())
-}
+    /// Use `url` as the server: activate the profile that already has it, or
+    /// point the active profile at it
+    pub fn set_active_server_url(&mut self, url: &str) {
+        self.server_url = url.to_string();
 
-/// Get the config file path (so users know where to edit)
-#[tauri::command]
-fn get_config_path_cmd() -> Result<String, String> {
-    get_config_path()
-        .map(|p| p.to_string_lossy().to_string())
-        .ok_or_else(|| "Could not determine config path".to_string())
-}
+        if let Some(index) = self.servers.iter().position(|profile| profile.url == url) {
+            self.active_server = index;
+        } else if let Some(profile) = self.servers.get_mut(self.active_server) {
+            profile.url = url.to_string();
+        } else {
+            let mut profile = ServerProfile::from_url(url);
+            profile.name = self.unique_profile_name(&profile.name);
+            self.servers.push(profile);
+            self.active_server = self.servers.len() - 1;
+        }
+    }
 
-/// Open the config file in the default editor
-#[tauri::command]
-fn open_config_file() -> Result<(), String> {
-    let config_path = get_config_path().ok_or("Could not determine config path")?;
+    /// Move `url` to the front of the recent servers, dropping the oldest past
+    /// the limit
+    pub fn remember_server(&mut self, url: &str) {
+        self.recent_servers.retain(|existing| existing != url);
+        self.recent_servers.insert(0, url.to_string());
+        self.recent_servers.truncate(MAX_RECENT_SERVERS);
+    }
 
-    // Ensure config exists
-    if !config_path.exists() {
-        save_config(&AppConfig::default())?;
+    /// Recent servers other than the active one, newest first
+    pub fn recent_servers(&self) -> Vec<String> {
+        self.recent_servers
+            .iter()
+            .filter(|url| **url != self.server_url)
+            .cloned()
+            .collect()
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg("-t")
-            .arg(&config_path)
-            .spawn()
-            .map_err(|e| format!("Failed to open config: {}", e))?;
+    /// Bring `servers`/`active_server` in line with `server_url`, e.g. after
+    /// `server_url` was edited by hand, and rename duplicate profile names.
+    /// Returns whether anything changed.
+    fn sync_server_profiles(&mut self) -> bool {
+        let renamed = self.dedupe_server_profile_names();
+        let in_sync = self
+            .servers
+            .get(self.active_server)
+            .is_some_and(|profile| profile.url == self.server_url);
+        if in_sync {
+            return renamed;
+        }
+
+        let server_url = self.server_url.clone();
+        self.set_active_server_url(&server_url);
+        true
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&config_path)
-            .spawn()
-            .map_err(|e| format!("Failed to open config: {}", e))?;
+    /// `name`, or `name (2)`, `name (3)`, ... if a profile already has it
+    fn unique_profile_name(&self, name: &str) -> String {
+        if self.server_profile_index(name).is_none() {
+            return name.to_string();
+        }
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| self.server_profile_index(candidate).is_none())
+            .unwrap()
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("notepad")
-            .arg(&config_path)
-            .spawn()
-            .map_err(|e| format!("Failed to open config: {}", e))?;
+    /// Rename profiles whose name an earlier profile already uses, so every
+    /// profile can be switched to by name
+    fn dedupe_server_profile_names(&mut self) -> bool {
+        let mut renamed = false;
+        for index in 1..self.servers.len() {
+            let name = self.servers[index].name.clone();
+            if self.servers[..index]
+                .iter()
+                .any(|profile| profile.name == name)
+            {
+                self.servers[index].name = self.unique_profile_name(&name);
+                renamed = true;
+            }
+        }
+        renamed
     }
 
-    Ok(())
-}
+    /// Name of the server profile for `server_url`
+    pub fn profile_name_for(&self, server_url: &str) -> Option<&str> {
+        self.servers
+            .iter()
+            .find(|profile| profile.url == server_url)
+            .map(|profile| profile.name.as_str())
+    }
 
-/// Open the config directory in file manager
-#[tauri::command]
-fn open_config_directory() -> Result<(), String> {
-    let config_dir = get_config_dir().ok_or("Could not determine config directory")?;
+    /// Index of the server profile called `name`
+    pub fn server_profile_index(&self, name: &str) -> Option<usize> {
+        self.servers.iter().position(|profile| profile.name == name)
+    }
 
-    // Ensure directory exists
-    fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
+    /// Add a server profile. Names must be unique so profiles can be switched
+    /// to by name.
+    pub fn add_server_profile(&mut self, name: &str, url: &str) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Profile name can't be empty".to_string());
+        }
+        if self.server_profile_index(name).is_some() {
+            return Err(format!("A profile named '{}' already exists", name));
+        }
 
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open")
-            .arg(&config_dir)
-            .spawn()
-            .map_err(|e| format!("Failed to open directory: {}", e))?;
+        self.servers.push(ServerProfile {
+            name: name.to_string(),
+            url: validate_server_url(url)?,
+        });
+        Ok(())
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open")
-            .arg(&config_dir)
-            .spawn()
-            .map_err(|e| format!("Failed to open directory: {}", e))?;
-    }
+    /// Remove the server profile called `name`. The active profile can't be
+    /// removed, so `server_url` always has a profile behind it.
+    pub fn remove_server_profile(&mut self, name: &str) -> Result<(), String> {
+        let index = self
+            .server_profile_index(name)
+            .ok_or_else(|| format!("No profile named '{}'", name))?;
+        if index == self.active_server {
+            return Err("Switch to another profile before removing this one".to_string());
+        }
 
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("explorer")
-            .arg(&config_dir)
-            .spawn()
-            .map_err(|e| format!("Failed to open directory: {}", e))?;
+        self.servers.remove(index);
+        if index < self.active_server {
+            self.active_server -= 1;
+        }
+        Ok(())
     }
+}
 
-    Ok(())
+/// The server a fresh config starts with: `ONYX_DEFAULT_SERVER_URL` from
+/// build time when set, otherwise Onyx Cloud
+fn default_server_url() -> String {
+    resolve_default_server_url(BUILD_DEFAULT_SERVER_URL)
 }
 
-/// Navigate to a specific path on the configured server
-#[tauri::command]
-fn navigate_to(window: tauri::WebviewWindow, state: tauri::State<ConfigState>, path: &str) {
-    let base_url = state.config.read().unwrap().server_url.clone();
-    let url = format!("{}{}", base_url, path);
-    let _ = window.eval(&format!("window.location.href = '{}'", url));
+/// Pick the default server from a build-time value, ignoring an empty or
+/// invalid one
+fn resolve_default_server_url(build_value: Option<&str>) -> String {
+    build_value
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .and_then(|url| match validate_server_url(url) {
+            Ok(url) => Some(url),
+            Err(e) => {
+                warn!("Ignoring ONYX_DEFAULT_SERVER_URL '{}': {}", url, e);
+                None
+            }
+        })
+        .unwrap_or_else(|| ONYX_CLOUD_URL.to_string())
 }
 
-/// Reload the current page
-#[tauri::command]
-fn reload_page(window: tauri::WebviewWindow) {
-    let _ = window.eval("window.location.reload()");
+fn default_window_title() -> String {
+    "Onyx".to_string()
 }
 
-/// Go back in history
-#[tauri::command]
-fn go_back(window: tauri::WebviewWindow) {
-    let _ = window.eval("window.history.back()");
+/// Trim a configured window title, falling back to the default when nothing
+/// is left
+fn normalize_window_title(title: &str) -> String {
+    let title = title.trim();
+    if title.is_empty() {
+        default_window_title()
+    } else {
+        title.to_string()
+    }
 }
 
-/// Go forward in history
+fn default_spellcheck() -> bool {
+    true
+}
+
+fn default_show_tray_icon() -> bool {
+    true
+}
+
+fn default_restore_session() -> bool {
+    true
+}
+
+fn default_toggle_on_summon() -> bool {
+    true
+}
+
+fn default_sso_callback_path() -> String {
+    SSO_CALLBACK_PATHS[0].to_string()
+}
+
+fn default_connection_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_idle_threshold_seconds() -> u64 {
+    300
+}
+
+fn default_window_width() -> f64 {
+    DEFAULT_WINDOW_WIDTH
+}
+
+fn default_window_height() -> f64 {
+    DEFAULT_WINDOW_HEIGHT
+}
+
+fn default_min_window_width() -> f64 {
+    MIN_WINDOW_WIDTH
+}
+
+fn default_min_window_height() -> f64 {
+    MIN_WINDOW_HEIGHT
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+/// The profile for the default server, named "Onyx Cloud" unless a build
+/// points elsewhere
+fn default_server_profile() -> ServerProfile {
+    let url = default_server_url();
+    if url == ONYX_CLOUD_URL {
+        ServerProfile {
+            name: "Onyx Cloud".to_string(),
+            url,
+        }
+    } else {
+        ServerProfile::from_url(&url)
+    }
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            server_url: default_server_url(),
+            servers: vec![default_server_profile()],
+            active_server: 0,
+            recent_servers: Vec::new(),
+            theme: ThemePreference::System,
+            window_title: default_window_title(),
+            title_follows_page: false,
+            shortcuts: HashMap::new(),
+            zoom_levels: HashMap::new(),
+            window_geometry: HashMap::new(),
+            close_to_tray: false,
+            toggle_on_summon: default_toggle_on_summon(),
+            show_tray_icon: default_show_tray_icon(),
+            restore_session: default_restore_session(),
+            tray_left_click: TrayLeftClick::default(),
+            always_on_top: false,
+            spellcheck: default_spellcheck(),
+            confirm_quit: false,
+            native_drag_drop: false,
+            launch_at_login: false,
+            connection_check_interval_secs: default_connection_check_interval_secs(),
+            keep_alive_minutes: None,
+            auto_reload_minutes: None,
+            idle_threshold_seconds: default_idle_threshold_seconds(),
+            default_width: DEFAULT_WINDOW_WIDTH,
+            default_height: DEFAULT_WINDOW_HEIGHT,
+            min_width: MIN_WINDOW_WIDTH,
+            min_height: MIN_WINDOW_HEIGHT,
+            background_color: None,
+            vibrancy: None,
+            user_agent: None,
+            proxy_url: None,
+            custom_ca_path: None,
+            danger_accept_invalid_certs: false,
+            basic_auth_user: None,
+            basic_auth_pass: None,
+            auto_check_updates: false,
+            sso_callback_path: default_sso_callback_path(),
+            log_level: default_log_level(),
+            developer_mode: false,
+            kiosk: false,
+            server_url_override: None,
+        }
+    }
+}
+
+/// The platform's standard config directory, if it can be determined
+fn standard_config_dir() -> Option<PathBuf> {
+    ProjectDirs::from("app", "onyx", "onyx-desktop").map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Where config may live, in order of preference: the standard directory,
+/// then a folder next to the executable, then one in the temp directory
+fn config_dir_candidates(
+    standard: Option<PathBuf>,
+    exe_path: Option<&Path>,
+    temp_dir: &Path,
+) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = standard.into_iter().collect();
+    if let Some(exe_dir) = exe_path.and_then(Path::parent) {
+        candidates.push(exe_dir.join(FALLBACK_CONFIG_DIR_NAME));
+    }
+    candidates.push(temp_dir.join(FALLBACK_CONFIG_DIR_NAME));
+    candidates
+}
+
+static CONFIG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Get the config directory: the first of `config_dir_candidates` that can be
+/// created, so settings persist even where the standard directory is
+/// unavailable (some sandboxes). Resolved once per run.
+fn resolve_config_dir() -> PathBuf {
+    CONFIG_DIR
+        .get_or_init(|| {
+            let exe_path = std::env::current_exe().ok();
+            let candidates = config_dir_candidates(
+                standard_config_dir(),
+                exe_path.as_deref(),
+                &std::env::temp_dir(),
+            );
+            candidates
+                .iter()
+                .find(|dir| fs::create_dir_all(dir).is_ok())
+                .or(candidates.last())
+                .cloned()
+                .unwrap_or_default()
+        })
+        .clone()
+}
+
+/// Config file from `--config-path`, set once at startup
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Kiosk mode from `--kiosk`, set once at startup
+static KIOSK_ARG: OnceLock<bool> = OnceLock::new();
+
+/// Get the full config file path, honoring `--config-path`
+fn get_config_path() -> PathBuf {
+    CONFIG_PATH_OVERRIDE
+        .get()
+        .cloned()
+        .unwrap_or_else(|| resolve_config_dir().join(CONFIG_FILE_NAME))
+}
+
+const USAGE: &str = "\
+Usage: onyx [OPTIONS]
+
+Options:
+  --server-url <URL>    Use this server for this session only (not saved)
+  --config-path <PATH>  Read and write config from PATH instead of the default
+  --kiosk               Run fullscreen and locked down for this session
+  -h, --help            Print this help";
+
+/// Options from the command line
+#[derive(Debug, Default)]
+struct CliArgs {
+    server_url: Option<String>,
+    config_path: Option<PathBuf>,
+    kiosk: bool,
+}
+
+/// Parse the arguments after the program name. Arguments we don't recognize
+/// (deep links, flags added by the OS) are left for other handlers.
+fn parse_cli_args(args: impl IntoIterator<Item = String>) -> Result<CliArgs, String> {
+    let mut cli = CliArgs::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => {
+                (flag.to_string(), Some(value.to_string()))
+            }
+            _ => (arg, None),
+        };
+
+        match flag.as_str() {
+            "--server-url" => {
+                let value = inline_value
+                    .or_else(|| args.next())
+                    .ok_or("--server-url requires a value")?;
+                cli.server_url =
+                    Some(validate_server_url(&value).map_err(|e| format!("--server-url: {}", e))?);
+            }
+            "--config-path" => {
+                let value = inline_value
+                    .or_else(|| args.next())
+                    .filter(|value| !value.is_empty())
+                    .ok_or("--config-path requires a value")?;
+                cli.config_path = Some(PathBuf::from(value));
+            }
+            "--kiosk" => cli.kiosk = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(cli)
+}
+
+/// Directory holding the rotating log files
+fn get_log_dir() -> PathBuf {
+    resolve_config_dir().join(LOG_DIR_NAME)
+}
+
+/// Send `tracing` output to stderr and to a daily log file in the config
+/// directory, keeping the last `MAX_LOG_FILES` files. Logging starts at `info`
+/// so config loading is captured; use the returned handle to apply the
+/// configured level. Keep the guard alive to flush the file on exit.
+fn init_logging() -> (
+    Option<WorkerGuard>,
+    reload::Handle<LevelFilter, tracing_subscriber::Registry>,
+) {
+    let (level, level_handle) = reload::Layer::new(LevelFilter::INFO);
+
+    let file_appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(get_log_dir())
+        .map_err(|e| eprintln!("Failed to open log file: {}", e))
+        .ok();
+    let (file_writer, guard) = match file_appender {
+        Some(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(level)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(file_writer.map(|writer| {
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(writer)
+        }))
+        .init();
+
+    (guard, level_handle)
+}
+
+/// Parse a `log_level` config value, falling back to `info`
+fn parse_log_level(raw: &str) -> LevelFilter {
+    LevelFilter::from_str(raw).unwrap_or_else(|_| {
+        warn!("Invalid log_level '{}'; using info", raw);
+        LevelFilter::INFO
+    })
+}
+
+/// Load config from file and apply the session server override, if any:
+/// `--server-url` (already validated) wins over `ONYX_SERVER_URL`
+fn load_config(server_url_arg: Option<String>) -> (AppConfig, bool) {
+    let (mut config, config_initialized) = load_config_file();
+
+    if let Ok(raw) = std::env::var(SERVER_URL_ENV_VAR) {
+        match validate_server_url(&raw) {
+            Ok(url) => config.server_url_override = Some(url),
+            Err(e) => warn!("Ignoring {}: {}", SERVER_URL_ENV_VAR, e),
+        }
+    }
+
+    if let Some(url) = server_url_arg {
+        config.server_url_override = Some(url);
+    }
+
+    // Managed settings win over both the file and the session override
+    let config = apply_managed_config(&config, managed_config());
+    (config, config_initialized)
+}
+
+/// Load config from file, or create default if it doesn't exist
+fn load_config_file() -> (AppConfig, bool) {
+    let config_path = get_config_path();
+    if !config_path.exists() {
+        return (AppConfig::default(), false);
+    }
+
+    match fs::read_to_string(&config_path) {
+        Ok(contents) => match parse_config(&contents) {
+            Ok((config, changed)) => {
+                if changed {
+                    if let Err(e) = save_config(&config) {
+                        error!("Failed to save migrated config: {}", e);
+                    }
+                }
+                (config, true)
+            }
+            Err(e) => {
+                error!("Failed to parse config {}: {}", config_path.display(), e);
+                backup_config_file(&config_path);
+                (AppConfig::default(), false)
+            }
+        },
+        Err(_) => (AppConfig::default(), false),
+    }
+}
+
+/// Parse config file contents, migrating and normalizing them. Returns the
+/// config and whether it differs from what was on disk.
+fn parse_config(contents: &str) -> Result<(AppConfig, bool), serde_json::Error> {
+    let mut value: serde_json::Value = serde_json::from_str(contents)?;
+    // Plaintext secrets are moved to the keychain when the config is re-saved
+    let plaintext_secrets = recombine_secrets(&mut value, read_secret);
+    let (mut config, migrated) = migrate_config(serde_json::from_value(value)?);
+    config.window_title = normalize_window_title(&config.window_title);
+    let synced = config.sync_server_profiles();
+    Ok((config, migrated || synced || plaintext_secrets))
+}
+
+/// Upgrade a config loaded from an older schema version to the current shape.
+/// Returns the config and whether anything changed (i.e. it should be re-saved).
+fn migrate_config(mut config: AppConfig) -> (AppConfig, bool) {
+    if config.version > CONFIG_VERSION {
+        warn!(
+            "Config version {} is newer than supported version {}; loading anyway",
+            config.version, CONFIG_VERSION
+        );
+        return (config, false);
+    }
+
+    let original_version = config.version;
+
+    // v0 -> v1: no shape changes, just stamp the version
+    if config.version < 1 {
+        config.version = 1;
+    }
+
+    // v1 -> v2: the single `server_url` becomes the "Default" server profile
+    if config.version < 2 {
+        if config.servers.is_empty() {
+            config.servers.push(ServerProfile {
+                name: "Default".to_string(),
+                url: config.server_url.clone(),
+            });
+            config.active_server = 0;
+        }
+        config.version = 2;
+    }
+
+    (config, config.version != original_version)
+}
+
+/// Move an unparseable config aside to `config.json.bak-<timestamp>` so the
+/// user can recover it. Best-effort: failures are logged and otherwise ignored.
+fn backup_config_file(config_path: &Path) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let file_name = config_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| CONFIG_FILE_NAME.to_string());
+    let backup_path = config_path.with_file_name(format!("{}.bak-{}", file_name, timestamp));
+
+    match fs::rename(config_path, &backup_path) {
+        Ok(()) => warn!("Backed up invalid config to {}", backup_path.display()),
+        Err(e) => error!("Failed to back up invalid config: {}", e),
+    }
+}
+
+/// Where administrators put settings users can't change
+fn managed_config_path() -> Option<PathBuf> {
+    if cfg!(target_os = "windows") {
+        std::env::var_os("ProgramData")
+            .map(|dir| PathBuf::from(dir).join("Onyx").join(CONFIG_FILE_NAME))
+    } else if cfg!(target_os = "macos") {
+        Some(PathBuf::from("/Library/Application Support/Onyx").join(CONFIG_FILE_NAME))
+    } else {
+        Some(PathBuf::from("/etc/onyx").join(CONFIG_FILE_NAME))
+    }
+}
+
+/// Settings locked by the administrator, read once at startup
+static MANAGED_CONFIG: OnceLock<serde_json::Map<String, serde_json::Value>> = OnceLock::new();
+
+fn managed_config() -> &'static serde_json::Map<String, serde_json::Value> {
+    MANAGED_CONFIG.get_or_init(load_managed_config)
+}
+
+/// Read the managed config file, if any. Each field is checked on its own and
+/// stored normalized (e.g. `server_url` as an origin); invalid ones are
+/// logged and skipped rather than locking in a broken value.
+fn load_managed_config() -> serde_json::Map<String, serde_json::Value> {
+    let Some(path) = managed_config_path() else {
+        return serde_json::Map::new();
+    };
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to read managed config {}: {}", path.display(), e);
+            }
+            return serde_json::Map::new();
+        }
+    };
+    let fields = match serde_json::from_str(&contents) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        Ok(_) => {
+            error!("Managed config {} must be a JSON object", path.display());
+            return serde_json::Map::new();
+        }
+        Err(e) => {
+            error!("Failed to parse managed config {}: {}", path.display(), e);
+            return serde_json::Map::new();
+        }
+    };
+
+    let defaults = AppConfig::default();
+    let managed: serde_json::Map<String, serde_json::Value> = fields
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let mut patch = serde_json::Map::new();
+            patch.insert(key.clone(), value);
+            let normalized = merge_config_patch(&defaults, &serde_json::Value::Object(patch))
+                .and_then(|mut config| {
+                    validate_config(&mut config)?;
+                    config_value_with_secrets(&config)
+                })
+                .map(|config| config[&key].clone());
+            match normalized {
+                Ok(value) => Some((key, value)),
+                Err(e) => {
+                    warn!("Ignoring managed setting {}: {}", key, e);
+                    None
+                }
+            }
+        })
+        .collect();
+
+    if !managed.is_empty() {
+        let keys: Vec<&str> = managed.keys().map(String::as_str).collect();
+        info!(
+            "Managed config {} locks: {}",
+            path.display(),
+            keys.join(", ")
+        );
+    }
+    managed
+}
+
+/// `config` with the managed settings laid over it. A managed `server_url`
+/// also beats a session override.
+fn apply_managed_config(
+    config: &AppConfig,
+    managed: &serde_json::Map<String, serde_json::Value>,
+) -> AppConfig {
+    if managed.is_empty() {
+        return config.clone();
+    }
+
+    let patch = serde_json::Value::Object(managed.clone());
+    let mut merged = merge_config_patch(config, &patch).unwrap_or_else(|e| {
+        error!("Failed to apply managed config: {}", e);
+        config.clone()
+    });
+    if !managed.contains_key("server_url") {
+        merged.server_url_override = config.server_url_override.clone();
+    }
+    merged.sync_server_profiles();
+    merged
+}
+
+/// Config keys a managed setting overrides: the setting itself, plus the
+/// server profiles when it's `server_url`, since those follow it
+fn managed_override_keys(managed: &serde_json::Map<String, serde_json::Value>) -> Vec<&str> {
+    let mut keys: Vec<&str> = managed.keys().map(String::as_str).collect();
+    if managed.contains_key("server_url") {
+        keys.extend(["servers", "active_server"]);
+    }
+    keys
+}
+
+/// Swap managed settings in a serialized config back to the user's own
+/// values from `user` (their config file), or to `defaults` where they never
+/// set one. Managed settings are only laid over the config in memory; saving
+/// them would keep them in force after the administrator lifts them.
+fn strip_managed_values(
+    value: &mut serde_json::Value,
+    managed: &serde_json::Map<String, serde_json::Value>,
+    user: Option<&serde_json::Value>,
+    defaults: &serde_json::Value,
+) {
+    let Some(fields) = value.as_object_mut() else {
+        return;
+    };
+    for key in managed_override_keys(managed) {
+        // Secrets live in the keychain, not the file, so leave them be
+        if SECRET_CONFIG_FIELDS.contains(&key) {
+            continue;
+        }
+        let own = user
+            .and_then(|user| user.get(key))
+            .or_else(|| defaults.get(key));
+        match own {
+            Some(own) => {
+                fields.insert(key.to_string(), own.clone());
+            }
+            None => {
+                fields.remove(key);
+            }
+        }
+    }
+}
+
+/// Undo the managed layer in a config about to be written, using the saved
+/// config at `config_path` as the user's layer
+fn restore_user_layer(value: &mut serde_json::Value, config_path: &Path) -> Result<(), String> {
+    let managed = managed_config();
+    if managed.is_empty() {
+        return Ok(());
+    }
+
+    let user = fs::read_to_string(config_path)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<serde_json::Value>(&contents).ok());
+    let defaults = config_value_with_secrets(&AppConfig::default())?;
+    strip_managed_values(value, managed, user.as_ref(), &defaults);
+    Ok(())
+}
+
+/// Reject a patch that changes a managed setting. Setting a managed field to
+/// its managed value is allowed, since nothing changes.
+fn check_managed_patch(
+    patch: &serde_json::Map<String, serde_json::Value>,
+    managed: &serde_json::Map<String, serde_json::Value>,
+) -> Result<(), String> {
+    for (key, value) in patch {
+        if managed.get(key).is_some_and(|locked| locked != value) {
+            return Err(managed_error(key));
+        }
+    }
+    Ok(())
+}
+
+fn managed_error(key: &str) -> String {
+    format!(
+        "{} is managed by your administrator and can't be changed",
+        key
+    )
+}
+
+/// Reject setting `key` to `value` if the administrator manages it
+fn ensure_unmanaged(key: &str, value: serde_json::Value) -> Result<(), String> {
+    let mut patch = serde_json::Map::new();
+    patch.insert(key.to_string(), value);
+    check_managed_patch(&patch, managed_config())
+}
+
+/// List the config fields the administrator has locked, so the settings UI
+/// can disable them
+#[tauri::command]
+fn get_managed_keys() -> Vec<String> {
+    managed_config().keys().cloned().collect()
+}
+
+/// Validate a user-supplied server URL and normalize it to its origin
+/// (`scheme://host[:port]`), dropping any path, query, or fragment
+fn validate_server_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err("URL must start with http:// or https://".to_string());
+    }
+
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+    if !parsed.has_host() {
+        return Err("URL must include a host".to_string());
+    }
+
+    Ok(parsed.origin().ascii_serialization())
+}
+
+/// Parse a server URL, requiring an explicit http:// or https:// scheme
+fn parse_server_url(raw: &str) -> Result<Url, String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("Invalid server URL '{}': {}", raw, e))?;
+
+    // `localhost:3000` parses as scheme "localhost", so check the scheme explicitly
+    match url.scheme() {
+        "http" | "https" if url.host_str().is_some() => Ok(url),
+        _ => Err(format!(
+            "Invalid server URL '{}': URL must start with http:// or https://",
+            raw
+        )),
+    }
+}
+
+/// Parse a proxy URL, requiring a scheme the webview supports
+fn parse_proxy_url(raw: &str) -> Result<Url, String> {
+    let url = Url::parse(raw.trim()).map_err(|e| format!("Invalid proxy URL '{}': {}", raw, e))?;
+
+    match url.scheme() {
+        "http" | "socks5" if url.host_str().is_some() => Ok(url),
+        _ => Err(format!(
+            "Invalid proxy URL '{}': URL must start with http:// or socks5://",
+            raw
+        )),
+    }
+}
+
+/// HTTP basic auth credentials for the saved server. Deliberately not
+/// `Debug`, so the password can't end up in a log line.
+#[derive(Clone)]
+struct BasicAuth {
+    user: String,
+    pass: String,
+}
+
+/// A URL safe to log, without credentials
+fn redact_url_credentials(url: &Url) -> String {
+    let mut redacted = url.clone();
+    let _ = redacted.set_username("");
+    let _ = redacted.set_password(None);
+    redacted.to_string()
+}
+
+/// Save config to file
+fn save_config(config: &AppConfig) -> Result<(), String> {
+    let config_path = get_config_path();
+
+    // Ensure config directory exists
+    if let Some(config_dir) = config_path.parent() {
+        fs::create_dir_all(config_dir)
+            .map_err(|e| format!("Failed to create config dir: {}", e))?;
+    }
+
+    let mut value = config_value_with_secrets(config)?;
+    restore_user_layer(&mut value, &config_path)?;
+    for (field, secret) in split_secrets(&mut value) {
+        if let Err(e) = write_secret(field, secret.as_deref()) {
+            // Better in plain text than lost
+            warn!("{}; keeping {} in config.json", e, field);
+            if let (Some(secret), Some(fields)) = (secret, value.as_object_mut()) {
+                fields.insert(field.to_string(), serde_json::Value::String(secret));
+            }
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&value)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+
+    write_file_atomically(&config_path, &json)
+}
+
+/// Write to a sibling temp file and rename over the real path so a crash
+/// mid-write never leaves a truncated file behind
+fn write_file_atomically(path: &Path, contents: &str) -> Result<(), String> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    fs::write(&tmp_path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), e))?;
+    fs::rename(&tmp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+/// The config with its secret fields, which plain serialization skips so
+/// they never reach webviews or exported files. Only for storing the config
+/// and working on it internally.
+fn config_value_with_secrets(config: &AppConfig) -> Result<serde_json::Value, String> {
+    let mut value =
+        serde_json::to_value(config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+    let fields = value
+        .as_object_mut()
+        .ok_or("Config did not serialize to an object")?;
+    fields.insert(
+        "basic_auth_pass".to_string(),
+        config.basic_auth_pass.clone().into(),
+    );
+    Ok(value)
+}
+
+/// Take the secret fields out of a serialized config, returning each one's
+/// value (`None` when unset, so its keychain entry can be cleared)
+fn split_secrets(value: &mut serde_json::Value) -> Vec<(&'static str, Option<String>)> {
+    let Some(fields) = value.as_object_mut() else {
+        return Vec::new();
+    };
+
+    SECRET_CONFIG_FIELDS
+        .iter()
+        .map(|&field| {
+            let secret = fields
+                .remove(field)
+                .and_then(|value| value.as_str().map(str::to_string));
+            (field, secret)
+        })
+        .collect()
+}
+
+/// Fill in secret fields missing from a serialized config using `lookup`
+/// (the keychain, outside of tests). Returns whether any secret was found in
+/// plain text instead.
+fn recombine_secrets(
+    value: &mut serde_json::Value,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> bool {
+    let Some(fields) = value.as_object_mut() else {
+        return false;
+    };
+
+    let mut plaintext = false;
+    for &field in SECRET_CONFIG_FIELDS {
+        match fields.get(field) {
+            Some(serde_json::Value::String(_)) => plaintext = true,
+            _ => {
+                if let Some(secret) = lookup(field) {
+                    fields.insert(field.to_string(), serde_json::Value::String(secret));
+                }
+            }
+        }
+    }
+    plaintext
+}
+
+/// The keychain entry for a secret field. A `--config-path` config gets its
+/// own entries so testing never touches the real ones.
+fn keychain_entry(field: &str) -> Result<keyring::Entry, String> {
+    let account = match CONFIG_PATH_OVERRIDE.get() {
+        Some(path) => format!("{}:{}", path.display(), field),
+        None => field.to_string(),
+    };
+    keyring::Entry::new(KEYCHAIN_SERVICE, &account)
+        .map_err(|e| format!("Failed to open keychain entry for {}: {}", field, e))
+}
+
+/// Read a secret field from the keychain, if it's stored there
+fn read_secret(field: &str) -> Option<String> {
+    let result = keychain_entry(field).and_then(|entry| match entry.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read {} from the keychain: {}", field, e)),
+    });
+    result.unwrap_or_else(|e| {
+        warn!("{}", e);
+        None
+    })
+}
+
+/// Store a secret field in the keychain, or remove it when `secret` is `None`
+fn write_secret(field: &str, secret: Option<&str>) -> Result<(), String> {
+    let entry = keychain_entry(field)?;
+    let result = match secret {
+        Some(secret) => entry.set_password(secret),
+        None => match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            result => result,
+        },
+    };
+    result.map_err(|e| format!("Failed to write {} to the keychain: {}", field, e))
+}
+
+// Global config state
+struct ConfigState {
+    config: RwLock<AppConfig>,
+    config_initialized: RwLock<bool>,
+}
+
+impl ConfigState {
+    /// The server URL windows should load, honoring any session override
+    fn server_url(&self) -> String {
+        self.config
+            .read()
+            .unwrap()
+            .effective_server_url()
+            .to_string()
+    }
+
+    fn zoom_level(&self) -> f64 {
+        self.config.read().unwrap().zoom_level()
+    }
+
+    fn user_agent(&self) -> Option<String> {
+        self.config.read().unwrap().user_agent.clone()
+    }
+
+    fn window_title(&self) -> String {
+        normalize_window_title(&self.config.read().unwrap().window_title)
+    }
+
+    fn proxy_url(&self) -> Option<Url> {
+        self.config.read().unwrap().proxy_url()
+    }
+
+    fn native_drag_drop(&self) -> bool {
+        self.config.read().unwrap().native_drag_drop
+    }
+
+    fn window_sizes(&self) -> WindowSizes {
+        self.config.read().unwrap().window_sizes()
+    }
+
+    fn kiosk_enabled(&self) -> bool {
+        self.config.read().unwrap().kiosk_enabled()
+    }
+
+    fn always_on_top(&self) -> bool {
+        self.config.read().unwrap().always_on_top
+    }
+
+    fn restore_session(&self) -> bool {
+        self.config.read().unwrap().restore_session
+    }
+
+    /// The configured window background color, if set and valid
+    fn background_color(&self) -> Option<Color> {
+        let raw = self.config.read().unwrap().background_color.clone()?;
+        let color = parse_hex_color(&raw);
+        if color.is_none() {
+            warn!("Invalid background_color '{}'; using the default", raw);
+        }
+        color
+    }
+
+    /// The configured vibrancy name, if any
+    fn vibrancy(&self) -> Option<String> {
+        self.config.read().unwrap().vibrancy.clone()
+    }
+
+    /// Whether vibrancy is turned off, so windows should be built opaque
+    fn vibrancy_disabled(&self) -> bool {
+        self.vibrancy()
+            .is_some_and(|name| name.trim().eq_ignore_ascii_case("none"))
+    }
+}
+
+/// Map a `vibrancy` config value to its material
+#[cfg(target_os = "macos")]
+fn vibrancy_material(name: &str) -> Option<NSVisualEffectMaterial> {
+    let material = match name.trim().to_ascii_lowercase().as_str() {
+        "titlebar" => NSVisualEffectMaterial::Titlebar,
+        "selection" => NSVisualEffectMaterial::Selection,
+        "menu" => NSVisualEffectMaterial::Menu,
+        "popover" => NSVisualEffectMaterial::Popover,
+        "sidebar" => NSVisualEffectMaterial::Sidebar,
+        "header-view" => NSVisualEffectMaterial::HeaderView,
+        "sheet" => NSVisualEffectMaterial::Sheet,
+        "window-background" => NSVisualEffectMaterial::WindowBackground,
+        "hud" => NSVisualEffectMaterial::HudWindow,
+        "fullscreen-ui" => NSVisualEffectMaterial::FullScreenUI,
+        "tooltip" => NSVisualEffectMaterial::Tooltip,
+        "content-background" => NSVisualEffectMaterial::ContentBackground,
+        "under-window-background" => NSVisualEffectMaterial::UnderWindowBackground,
+        "under-page-background" => NSVisualEffectMaterial::UnderPageBackground,
+        _ => return None,
+    };
+    Some(material)
+}
+
+/// Parse a `#RRGGBB` or `#RRGGBBAA` hex color
+fn parse_hex_color(raw: &str) -> Option<Color> {
+    let hex = raw.trim().strip_prefix('#')?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+    let alpha = if hex.len() == 8 { channel(6)? } else { 0xff };
+    Some(Color(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+/// Notify every window that the config was saved
+fn emit_config_changed(app: &AppHandle, config: &AppConfig) {
+    apply_window_title(app, config);
+    if let Err(e) = app.emit(CONFIG_CHANGED_EVENT, config) {
+        error!("Failed to emit {}: {}", CONFIG_CHANGED_EVENT, e);
+    }
+}
+
+/// Retitle every open app window so title changes show up without a restart
+fn apply_window_title(app: &AppHandle, config: &AppConfig) {
+    let page_titles = app.state::<PageTitleState>();
+    let page_titles = page_titles.titles.lock().unwrap();
+    for (label, window) in app.webview_windows() {
+        if !is_app_window(&label) {
+            continue;
+        }
+        let title = window_title_for_page(config, page_titles.get(&label).map(String::as_str));
+        if let Err(e) = window.set_title(&title) {
+            warn!("Failed to set title of {}: {}", label, e);
+        }
+    }
+}
+
+/// The title of a window showing a page with the given title. Without
+/// `title_follows_page`, or before the page has a title, this is just the
+/// configured title.
+fn window_title_for_page(config: &AppConfig, page_title: Option<&str>) -> String {
+    let app_title = normalize_window_title(&config.window_title);
+    let page_title = page_title.map(str::trim).unwrap_or_default();
+    if !config.title_follows_page || page_title.is_empty() || page_title == app_title {
+        return app_title;
+    }
+    format!("{} \u{2014} {}", page_title, app_title)
+}
+
+/// Each app window's page title, as reported by the injected page-title script
+struct PageTitleState {
+    titles: Mutex<HashMap<String, String>>,
+}
+
+/// Record the calling window's page title and retitle the window if the
+/// title follows the page
+#[tauri::command]
+fn set_page_title(
+    window: tauri::WebviewWindow,
+    config_state: tauri::State<ConfigState>,
+    state: tauri::State<PageTitleState>,
+    title: String,
+) {
+    if !is_app_window(window.label()) {
+        return;
+    }
+
+    let window_title = {
+        let config = config_state.config.read().unwrap();
+        window_title_for_page(&config, Some(&title))
+    };
+    state
+        .titles
+        .lock()
+        .unwrap()
+        .insert(window.label().to_string(), title);
+
+    if let Err(e) = window.set_title(&window_title) {
+        warn!("Failed to set title of {}: {}", window.label(), e);
+    }
+}
+
+/// Clamp, persist, and apply a zoom level for `server_url` to every window
+/// showing that server
+fn apply_zoom_level(app: &AppHandle, server_url: &str, level: f64) -> Result<f64, String> {
+    let level = if level.is_finite() {
+        level.clamp(MIN_ZOOM_LEVEL, MAX_ZOOM_LEVEL)
+    } else {
+        DEFAULT_ZOOM_LEVEL
+    };
+    if managed_config().contains_key("zoom_levels") {
+        return Err(managed_error("zoom_levels"));
+    }
+
+    {
+        let state = app.state::<ConfigState>();
+        let mut config = state.config.write().unwrap();
+        config.zoom_levels.insert(server_url.to_string(), level);
+        save_config(&config)?;
+        emit_config_changed(app, &config);
+    }
+
+    for (label, window) in app.webview_windows() {
+        if window_server_url(app, &label) == server_url {
+            let _ = window.set_zoom(level);
+        }
+    }
+
+    Ok(level)
+}
+
+/// The server of the focused window, which zoom shortcuts and menu items act on
+fn focused_server_url(app: &AppHandle) -> String {
+    match focused_window(app) {
+        Some(window) => window_server_url(app, window.label()),
+        None => app.state::<ConfigState>().server_url(),
+    }
+}
+
+/// Step the focused window's zoom level up or down by `ZOOM_STEP`
+fn adjust_zoom_level(app: &AppHandle, delta: f64) {
+    let server_url = focused_server_url(app);
+    let current = app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .zoom_level_for(&server_url);
+    if let Err(e) = apply_zoom_level(app, &server_url, current + delta) {
+        error!("Failed to set zoom level: {}", e);
+    }
+}
+
+/// Put the focused window's server back at 100% zoom
+fn reset_focused_zoom_level(app: &AppHandle) {
+    if let Err(e) = apply_zoom_level(app, &focused_server_url(app), DEFAULT_ZOOM_LEVEL) {
+        error!("Failed to reset zoom level: {}", e);
+    }
+}
+
+// Recently visited chats, most recent first, shown in the tray menu
+struct RecentChatsState {
+    chat_ids: RwLock<VecDeque<String>>,
+}
+
+fn load_recent_chats() -> VecDeque<String> {
+    fs::read_to_string(resolve_config_dir().join(RECENT_CHATS_FILE_NAME))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<VecDeque<String>>(&contents).ok())
+        .map(|mut chat_ids| {
+            chat_ids.truncate(MAX_RECENT_CHATS);
+            chat_ids
+        })
+        .unwrap_or_default()
+}
+
+fn save_recent_chats(chat_ids: &VecDeque<String>) -> Result<(), String> {
+    let config_dir = resolve_config_dir();
+    fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
+
+    let json = serde_json::to_string_pretty(chat_ids)
+        .map_err(|e| format!("Failed to serialize recent chats: {}", e))?;
+    write_file_atomically(&config_dir.join(RECENT_CHATS_FILE_NAME), &json)
+}
+
+/// The chat session a web app URL points at, e.g. `/chat?chatId=<id>`
+fn chat_id_from_url(url: &Url) -> Option<String> {
+    if !url.path().starts_with("/chat") && !url.path().starts_with("/app") {
+        return None;
+    }
+
+    url.query_pairs()
+        .find(|(key, _)| key == "chatId")
+        .map(|(_, value)| value.into_owned())
+        .filter(|chat_id| !chat_id.is_empty())
+}
+
+/// Move `chat_id` to the front of the recent list, then persist it and refresh the tray
+fn record_recent_chat(app: &AppHandle, chat_id: String) {
+    let state = app.state::<RecentChatsState>();
+    {
+        let mut chat_ids = state.chat_ids.write().unwrap();
+        if chat_ids.front() == Some(&chat_id) {
+            return;
+        }
+
+        chat_ids.retain(|existing| existing != &chat_id);
+        chat_ids.push_front(chat_id);
+        chat_ids.truncate(MAX_RECENT_CHATS);
+
+        if let Err(e) = save_recent_chats(&chat_ids) {
+            error!("Failed to save recent chats: {}", e);
+        }
+    }
+
+    refresh_tray_menu(app);
+}
+
+/// An app window as it was when the app last quit. Size and position are in
+/// logical pixels.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedWindow {
+    label: String,
+    url: String,
+    width: f64,
+    height: f64,
+    x: f64,
+    y: f64,
+}
+
+fn load_window_session() -> Vec<SavedWindow> {
+    fs::read_to_string(resolve_config_dir().join(WINDOW_SESSION_FILE_NAME))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<Vec<SavedWindow>>(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn save_window_session(windows: &[SavedWindow]) -> Result<(), String> {
+    let config_dir = resolve_config_dir();
+    fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
+
+    let json = serde_json::to_string_pretty(windows)
+        .map_err(|e| format!("Failed to serialize window session: {}", e))?;
+    write_file_atomically(&config_dir.join(WINDOW_SESSION_FILE_NAME), &json)
+}
+
+/// Snapshot the main and `onyx-<uuid>` windows that are showing the web app.
+/// The settings and quick-chat windows aren't part of the session.
+fn capture_window_session(app: &AppHandle) -> Vec<SavedWindow> {
+    let mut windows: Vec<SavedWindow> = app
+        .webview_windows()
+        .into_values()
+        .filter(|window| is_app_window(window.label()))
+        .filter_map(|window| {
+            let url = window.url().ok()?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return None;
+            }
+
+            let scale = window.scale_factor().ok()?;
+            let size = window.inner_size().ok()?.to_logical::<f64>(scale);
+            let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+            Some(SavedWindow {
+                label: window.label().to_string(),
+                // Never store credentials, whatever put them in the URL
+                url: redact_url_credentials(&url),
+                width: size.width,
+                height: size.height,
+                x: position.x,
+                y: position.y,
+            })
+        })
+        .collect();
+
+    // Keep restore order stable: main first, then the others by label
+    windows.sort_by(|a, b| (a.label != "main", &a.label).cmp(&(b.label != "main", &b.label)));
+    windows
+}
+
+/// Rewrite the saved session as app windows load pages or close, so it's
+/// current even if Onyx doesn't quit cleanly. `closed` is a window being
+/// destroyed, left out of the snapshot. Skipped once quitting has saved the
+/// final session, since the windows closing after that aren't the user's doing.
+/// Closing the last window also keeps the saved session: that quits the app,
+/// and the window should come back next launch.
+fn persist_window_session(app: &AppHandle, closed: Option<&str>) {
+    if *app.state::<QuitState>().flushed.lock().unwrap() {
+        return;
+    }
+
+    let mut windows = capture_window_session(app);
+    if let Some(closed) = closed {
+        windows.retain(|window| window.label != closed);
+        let others_open = app
+            .webview_windows()
+            .keys()
+            .any(|label| is_app_window(label) && label != closed);
+        if !others_open {
+            return;
+        }
+    }
+    if let Err(e) = save_window_session(&windows) {
+        error!("Failed to save window session: {}", e);
+    }
+}
+
+/// Reopen the windows saved at last quit. Only pages on the current server
+/// or another server profile are restored, the latter staying on that
+/// server; the main window keeps the geometry from the window-state plugin
+/// and just navigates back to where it was.
+fn restore_window_session(app: &AppHandle) {
+    let saved = load_window_session();
+    if saved.is_empty() {
+        return;
+    }
+
+    let state = app.state::<ConfigState>();
+    let server_url = state.server_url();
+    let server = match parse_server_url(&server_url) {
+        Ok(url) => url,
+        Err(_) => return,
+    };
+    let profiles: Vec<Url> = state
+        .config
+        .read()
+        .unwrap()
+        .servers
+        .iter()
+        .filter_map(|profile| parse_server_url(&profile.url).ok())
+        .collect();
+    let user_agent = state.user_agent();
+    let proxy_url = state.proxy_url();
+    let native_drag_drop = state.native_drag_drop();
+    #[cfg(target_os = "linux")]
+    let background_color = state.background_color();
+    let transparent = !state.vibrancy_disabled();
+    let sizes = state.window_sizes();
+    let title = state.window_title();
+
+    for window in saved {
+        let Ok(url) = parse_server_url(&window.url) else {
+            continue;
+        };
+        let window_server = if url.origin() == server.origin() {
+            None
+        } else {
+            match profiles
+                .iter()
+                .find(|profile| profile.origin() == url.origin())
+            {
+                Some(profile) => Some(profile.origin().ascii_serialization()),
+                None => continue,
+            }
+        };
+
+        if window.label == "main" {
+            // The main window always follows the active server
+            if window_server.is_some() {
+                continue;
+            }
+            if let Some(main) = app.get_webview_window("main") {
+                let _ = main.navigate(url);
+            }
+            continue;
+        }
+
+        let builder = WebviewWindowBuilder::new(app, &window.label, WebviewUrl::External(url))
+            .title(&title)
+            .inner_size(window.width, window.height)
+            .position(window.x, window.y)
+            .min_inner_size(sizes.min_width, sizes.min_height)
+            .transparent(transparent);
+
+        #[cfg(target_os = "macos")]
+        let builder = builder
+            .title_bar_style(tauri::TitleBarStyle::Overlay)
+            .hidden_title(true);
+
+        #[cfg(target_os = "linux")]
+        let builder =
+            builder.background_color(background_color.unwrap_or(DEFAULT_BACKGROUND_COLOR));
+
+        let builder = apply_drag_drop(builder, native_drag_drop);
+        let builder = apply_download_handler(apply_proxy(
+            apply_user_agent(builder, user_agent.as_deref()),
+            proxy_url.clone(),
+        ));
+
+        match builder.build() {
+            Ok(restored) => {
+                let zoom_level = state
+                    .config
+                    .read()
+                    .unwrap()
+                    .zoom_level_for(window_server.as_deref().unwrap_or(&server_url));
+                let _ = restored.set_zoom(zoom_level);
+
+                if let Some(window_server) = window_server {
+                    app.state::<WindowServerState>()
+                        .servers
+                        .write()
+                        .unwrap()
+                        .insert(window.label.clone(), window_server);
+                }
+
+                apply_window_theme(&restored);
+                if state.always_on_top() {
+                    let _ = apply_always_on_top(app, &restored, true);
+                }
+            }
+            Err(e) => error!("Failed to restore window {}: {}", window.label, e),
+        }
+    }
+}
+
+/// User stylesheet from the config directory, cached by modification time
+struct CustomCssState {
+    cache: Mutex<Option<(SystemTime, String)>>,
+}
+
+/// Script that installs (or updates) the user's `custom.css`, or `None` when
+/// the file doesn't exist. Only re-reads the file when its mtime changes.
+fn custom_css_script(app: &AppHandle) -> Option<String> {
+    let state = app.state::<CustomCssState>();
+    let mut cache = state.cache.lock().unwrap();
+
+    let path = resolve_config_dir().join(CUSTOM_CSS_FILE_NAME);
+    let modified = match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified,
+        Err(_) => {
+            *cache = None;
+            return None;
+        }
+    };
+
+    if let Some((cached_modified, script)) = cache.as_ref() {
+        if *cached_modified == modified {
+            return Some(script.clone());
+        }
+    }
+
+    let css = match fs::read_to_string(&path) {
+        Ok(css) => css,
+        Err(e) => {
+            error!("Failed to read {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    let css = serde_json::to_string(&css).ok()?;
+    let script = format!(
+        r#"(function () {{
+  let style = document.getElementById("{id}");
+  if (!style) {{
+    style = document.createElement("style");
+    style.id = "{id}";
+    (document.head || document.documentElement).appendChild(style);
+  }}
+  style.textContent = {css};
+}})();"#,
+        id = CUSTOM_CSS_STYLE_ID,
+        css = css
+    );
+
+    *cache = Some((modified, script.clone()));
+    Some(script)
+}
+
+/// Script that turns spell checking on or off for every text field on the
+/// page, short of fields that set `spellcheck` themselves
+fn spellcheck_script(enabled: bool) -> String {
+    format!(
+        "if (document.documentElement) document.documentElement.spellcheck = {};",
+        enabled
+    )
+}
+
+/// Apply the spellcheck setting to every open page. WebKitGTK also has an
+/// engine-wide switch, shared by all windows.
+fn apply_spellcheck(app: &AppHandle, enabled: bool) {
+    let script = spellcheck_script(enabled);
+    for window in app.webview_windows().values() {
+        if window.label() != SETTINGS_WINDOW_LABEL {
+            let _ = window.eval(&script);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.with_webview(move |webview| {
+            use webkit2gtk::{WebContextExt, WebViewExt};
+
+            if let Some(context) = webview.inner().context() {
+                context.set_spell_checking_enabled(enabled);
+            }
+        });
+    }
+}
+
+/// Check or uncheck the Edit menu's spellcheck item
+fn refresh_spellcheck_menu(app: &AppHandle, enabled: bool) {
+    let item = app
+        .menu()
+        .and_then(|menu| find_submenu(&menu, "Edit").ok().flatten())
+        .and_then(|(_, submenu)| submenu.get(SPELLCHECK_MENU_ID))
+        .and_then(|item| item.as_check_menuitem().cloned());
+
+    if let Some(item) = item {
+        let _ = item.set_checked(enabled);
+    }
+}
+
+/// Get whether spell checking is on
+#[tauri::command]
+fn get_spellcheck(state: tauri::State<ConfigState>) -> bool {
+    state.config.read().unwrap().spellcheck
+}
+
+/// Turn spell checking on or off for all windows and remember the choice
+#[tauri::command]
+fn set_spellcheck(app: AppHandle, enabled: bool) -> Result<bool, String> {
+    ensure_unmanaged("spellcheck", enabled.into())?;
+    {
+        let state = app.state::<ConfigState>();
+        let mut config = state.config.write().unwrap();
+        config.spellcheck = enabled;
+        save_config(&config)?;
+        emit_config_changed(&app, &config);
+    }
+
+    apply_spellcheck(&app, enabled);
+    refresh_spellcheck_menu(&app, enabled);
+    Ok(enabled)
+}
+
+/// Label of the app window the user focused last, for returning to it
+struct LastFocusedWindowState {
+    label: Mutex<Option<String>>,
+}
+
+/// Servers of windows opened on something other than the active server,
+/// keyed by window label. Windows not listed follow the active server.
+struct WindowServerState {
+    servers: RwLock<HashMap<String, String>>,
+}
+
+/// The server a window belongs to: the one it was opened on, or the active one
+fn window_server_url(app: &AppHandle, label: &str) -> String {
+    let pinned = app
+        .state::<WindowServerState>()
+        .servers
+        .read()
+        .unwrap()
+        .get(label)
+        .cloned();
+    pinned.unwrap_or_else(|| app.state::<ConfigState>().server_url())
+}
+
+/// Whether a window was opened on a server other than the active one
+fn is_pinned_to_server(app: &AppHandle, label: &str) -> bool {
+    app.state::<WindowServerState>()
+        .servers
+        .read()
+        .unwrap()
+        .contains_key(label)
+}
+
+/// Whether a window shows the app itself, as opposed to settings or quick chat
+fn is_app_window(label: &str) -> bool {
+    label == "main" || label.starts_with("onyx-")
+}
+
+/// The window that showing the app brings forward: the main window if it
+/// exists, otherwise the most recently focused app window (or any visible one)
+fn summon_target_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let last_focused = app
+        .state::<LastFocusedWindowState>()
+        .label
+        .lock()
+        .unwrap()
+        .clone();
+
+    app.get_webview_window("main").or_else(|| {
+        let windows: Vec<WebviewWindow> = app
+            .webview_windows()
+            .into_values()
+            .filter(|window| is_app_window(window.label()))
+            .collect();
+
+        windows
+            .iter()
+            .find(|window| Some(window.label()) == last_focused.as_deref())
+            .or_else(|| {
+                windows
+                    .iter()
+                    .find(|window| window.is_visible().unwrap_or(false))
+            })
+            .or_else(|| windows.first())
+            .cloned()
+    })
+}
+
+/// Bring the app forward (see `summon_target_window`). A new window is opened
+/// only when no app window exists at all.
+fn focus_main_window(app: &AppHandle) {
+    if let Some(window) = summon_target_window(app) {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    } else {
+        trigger_new_window(app);
+    }
+}
+
+/// Handle the show-app shortcut: hide the app window if it's already in
+/// front and `toggle_on_summon` is on, otherwise bring the app forward
+fn toggle_main_window(app: &AppHandle) {
+    let toggle = app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .toggle_on_summon;
+
+    if toggle {
+        if let Some(window) = summon_target_window(app) {
+            let in_front = window.is_visible().unwrap_or(false)
+                && !window.is_minimized().unwrap_or(false)
+                && window.is_focused().unwrap_or(false);
+            if in_front {
+                let _ = window.hide();
+                return;
+            }
+        }
+    }
+
+    focus_main_window(app);
+}
+
+/// Apply the configured User-Agent to a window builder, keeping the
+/// platform default when none is set
+fn apply_user_agent<'a, M: Manager<Wry>>(
+    builder: WebviewWindowBuilder<'a, Wry, M>,
+    user_agent: Option<&str>,
+) -> WebviewWindowBuilder<'a, Wry, M> {
+    match user_agent {
+        Some(user_agent) => builder.user_agent(user_agent),
+        None => builder,
+    }
+}
+
+/// Route a window's traffic through the configured proxy, keeping the
+/// system proxy when none is set. macOS webviews always use the system proxy.
+fn apply_proxy<'a, M: Manager<Wry>>(
+    builder: WebviewWindowBuilder<'a, Wry, M>,
+    proxy_url: Option<Url>,
+) -> WebviewWindowBuilder<'a, Wry, M> {
+    match proxy_url {
+        Some(proxy_url) => builder.proxy_url(proxy_url),
+        None => builder,
+    }
+}
+
+/// Hand file drops to the page's own HTML5 handlers when configured, instead
+/// of the app turning them into `files-dropped` events
+fn apply_drag_drop<'a, M: Manager<Wry>>(
+    builder: WebviewWindowBuilder<'a, Wry, M>,
+    native_drag_drop: bool,
+) -> WebviewWindowBuilder<'a, Wry, M> {
+    if native_drag_drop {
+        builder.disable_drag_drop_handler()
+    } else {
+        builder
+    }
+}
+
+/// A file dropped onto a window, as sent to the web app
+#[derive(Debug, Clone, Serialize)]
+struct DroppedFile {
+    path: String,
+    name: String,
+    size: u64,
+    mime_type: String,
+    /// The contents as a data URL, for files up to 10 MB
+    data_url: Option<String>,
+}
+
+/// Payload of `files-dropped`: the files plus the drop point in logical pixels
+#[derive(Debug, Clone, Serialize)]
+struct FilesDroppedPayload {
+    files: Vec<DroppedFile>,
+    x: f64,
+    y: f64,
+}
+
+/// Describe a dropped file, inlining its contents when small. Directories
+/// and unreadable paths are skipped.
+fn read_dropped_file(path: &Path) -> Option<DroppedFile> {
+    let metadata = fs::metadata(path)
+        .ok()
+        .filter(|metadata| metadata.is_file())?;
+    let mime_type = mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string();
+
+    let data_url = if metadata.len() <= MAX_INLINE_DROP_BYTES {
+        match fs::read(path) {
+            Ok(contents) => Some(format!(
+                "data:{};base64,{}",
+                mime_type,
+                base64::engine::general_purpose::STANDARD.encode(contents)
+            )),
+            Err(e) => {
+                warn!("Failed to read dropped file {}: {}", path.display(), e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    Some(DroppedFile {
+        path: path.display().to_string(),
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size: metadata.len(),
+        mime_type,
+        data_url,
+    })
+}
+
+/// Forward files dropped onto an app window to its page as `files-dropped`
+fn forward_dropped_files(window: &tauri::Window, paths: Vec<PathBuf>, position: (f64, f64)) {
+    let app = window.app_handle().clone();
+    let label = window.label().to_string();
+
+    // Reading files can take a while; keep it off the event loop
+    tauri::async_runtime::spawn_blocking(move || {
+        let files: Vec<DroppedFile> = paths
+            .iter()
+            .filter_map(|path| read_dropped_file(path))
+            .collect();
+        if files.is_empty() {
+            return;
+        }
+
+        let payload = FilesDroppedPayload {
+            files,
+            x: position.0,
+            y: position.1,
+        };
+        if let Err(e) = app.emit_to(label.as_str(), FILES_DROPPED_EVENT, payload) {
+            error!("Failed to emit {}: {}", FILES_DROPPED_EVENT, e);
+        }
+    });
+}
+
+/// The theme a window is showing, honoring the configured preference
+fn window_theme(window: &WebviewWindow) -> Theme {
+    let preference = window.state::<ConfigState>().config.read().unwrap().theme;
+    preference
+        .forced()
+        .or_else(|| window.theme().ok())
+        .unwrap_or(Theme::Dark)
+}
+
+fn theme_name(theme: Theme) -> &'static str {
+    match theme {
+        Theme::Light => "light",
+        _ => "dark",
+    }
+}
+
+/// Apply the configured theme preference to a new window, then match its
+/// native chrome to the resulting theme
+fn apply_window_theme(window: &WebviewWindow) {
+    let preference = window.state::<ConfigState>().config.read().unwrap().theme;
+    let _ = window.set_theme(preference.forced());
+    apply_theme_chrome(window);
+}
+
+/// Match a window's chrome to its theme: the vibrancy material on macOS and
+/// the background color elsewhere
+fn apply_theme_chrome(window: &WebviewWindow) {
+    let theme = window_theme(window);
+
+    #[cfg(target_os = "macos")]
+    {
+        let state = window.state::<ConfigState>();
+        if state.vibrancy_disabled() {
+            let _ = clear_vibrancy(window);
+            return;
+        }
+
+        let configured = state.vibrancy().and_then(|name| {
+            let material = vibrancy_material(&name);
+            if material.is_none() {
+                warn!("Unknown vibrancy '{}'; using the default", name);
+            }
+            material
+        });
+        let material = configured.unwrap_or(match theme {
+            Theme::Light => NSVisualEffectMaterial::WindowBackground,
+            _ => NSVisualEffectMaterial::Sidebar,
+        });
+        let _ = apply_vibrancy(window, material, None, None);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let configured = window.state::<ConfigState>().background_color();
+        let color = configured.unwrap_or(match theme {
+            Theme::Light => LIGHT_BACKGROUND_COLOR,
+            _ => DEFAULT_BACKGROUND_COLOR,
+        });
+        let _ = window.set_background_color(Some(color));
+    }
+}
+
+/// Refresh every app window's chrome after a theme change and tell the web
+/// app about it
+fn apply_theme_to_all_windows(app: &AppHandle) {
+    for window in app.webview_windows().values() {
+        if is_app_window(window.label()) {
+            apply_theme_chrome(window);
+        }
+
+        let theme = theme_name(window_theme(window));
+        let _ = window.emit_to(window.label(), THEME_CHANGED_EVENT, theme);
+    }
+}
+
+/// The theme the calling window is showing: `light` or `dark`
+#[tauri::command]
+fn get_theme(window: tauri::WebviewWindow) -> &'static str {
+    theme_name(window_theme(&window))
+}
+
+/// Payload of the download events emitted to the window that started it
+#[derive(Debug, Clone, Serialize)]
+struct DownloadPayload {
+    url: String,
+    path: Option<String>,
+    success: Option<bool>,
+}
+
+/// Save web app downloads to the user's Downloads folder. The webview only
+/// reports start and finish, so those are the progress events sent to the
+/// page as `download-started` and `download-finished`.
+fn apply_download_handler<'a, M: Manager<Wry>>(
+    builder: WebviewWindowBuilder<'a, Wry, M>,
+) -> WebviewWindowBuilder<'a, Wry, M> {
+    builder.on_download(|webview, event| {
+        match event {
+            DownloadEvent::Requested { url, destination } => {
+                let Some(download_dir) =
+                    UserDirs::new().and_then(|dirs| dirs.download_dir().map(Path::to_path_buf))
+                else {
+                    warn!("Could not determine Downloads folder; using the default location");
+                    return true;
+                };
+
+                let file_name = download_file_name(&url, destination);
+                *destination = unique_download_path(&download_dir, &file_name);
+
+                let _ = webview.emit_to(
+                    webview.label(),
+                    DOWNLOAD_STARTED_EVENT,
+                    DownloadPayload {
+                        url: url.to_string(),
+                        path: Some(destination.display().to_string()),
+                        success: None,
+                    },
+                );
+            }
+            DownloadEvent::Finished { url, path, success } => {
+                if success {
+                    if let Some(path) = &path {
+                        reveal_in_file_manager(path);
+                    }
+                } else {
+                    error!("Download failed: {}", url);
+                }
+
+                let _ = webview.emit_to(
+                    webview.label(),
+                    DOWNLOAD_FINISHED_EVENT,
+                    DownloadPayload {
+                        url: url.to_string(),
+                        path: path.map(|path| path.display().to_string()),
+                        success: Some(success),
+                    },
+                );
+            }
+            _ => {}
+        }
+        true
+    })
+}
+
+/// The suggested file name for a download: the webview's proposed
+/// destination, then the last URL path segment
+fn download_file_name(url: &Url, destination: &Path) -> String {
+    destination
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .or_else(|| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .filter(|segment| !segment.is_empty())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "download".to_string())
+}
+
+/// `dir/name`, or `dir/name (1).ext`, `dir/name (2).ext`, ... when taken
+fn unique_download_path(dir: &Path, file_name: &str) -> PathBuf {
+    let candidate = dir.join(file_name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let name = Path::new(file_name);
+    let stem = name
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|counter| dir.join(format!("{} ({}){}", stem, counter, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Show a file selected in Finder / Explorer, or its folder on Linux
+fn reveal_in_file_manager(path: &Path) {
+    #[cfg(target_os = "macos")]
+    {
+        let _ = Command::new("open").arg("-R").arg(path).spawn();
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(parent) = path.parent() {
+            let _ = Command::new("xdg-open").arg(parent).spawn();
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let _ = Command::new("explorer")
+            .arg(format!("/select,{}", path.display()))
+            .spawn();
+    }
+}
+
+/// File name for a PDF of a window's page, from its title
+#[cfg(target_os = "linux")]
+fn pdf_file_name(window: &WebviewWindow) -> String {
+    let title: String = window
+        .title()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let title = title.trim();
+    format!("{}.pdf", if title.is_empty() { "Onyx" } else { title })
+}
+
+/// Render a page to a PDF file with WebKitGTK's print-to-file backend
+#[cfg(target_os = "linux")]
+async fn export_pdf(window: &WebviewWindow, path: &Path) -> Result<(), String> {
+    let uri = Url::from_file_path(path)
+        .map_err(|_| format!("PDF path must be absolute: {}", path.display()))?
+        .to_string();
+    let (sender, receiver) = std::sync::mpsc::channel();
+
+    window
+        .with_webview(move |webview| {
+            use webkit2gtk::{PrintOperation, PrintOperationExt};
+
+            let settings = gtk::PrintSettings::new();
+            settings.set_printer("Print to File");
+            settings.set(gtk::PRINT_SETTINGS_OUTPUT_FILE_FORMAT, Some("pdf"));
+            settings.set(gtk::PRINT_SETTINGS_OUTPUT_URI, Some(&uri));
+
+            let operation = PrintOperation::new(&webview.inner());
+            operation.set_print_settings(&settings);
+
+            // `finished` also fires after `failed`; only the first result is read
+            let failed = sender.clone();
+            operation.connect_failed(move |_, error| {
+                let _ = failed.send(Err(error.to_string()));
+            });
+            operation.connect_finished(move |_| {
+                let _ = sender.send(Ok(()));
+            });
+            operation.print();
+        })
+        .map_err(|e| format!("Failed to access webview: {}", e))?;
+
+    tauri::async_runtime::spawn_blocking(move || receiver.recv())
+        .await
+        .map_err(|e| format!("Failed to print page to PDF: {}", e))?
+        .map_err(|_| "Failed to print page to PDF: the page was closed".to_string())?
+        .map_err(|e| format!("Failed to print page to PDF: {}", e))
+}
+
+/// Save the calling window's page as a PDF in Downloads, named after the
+/// page, and return where it was saved. The page doesn't get to pick the
+/// path, so it can't write elsewhere. Only Linux can export directly;
+/// elsewhere this opens the system print dialog, whose Save as PDF option
+/// does the same, and returns `None`.
+#[tauri::command]
+async fn print_to_pdf(window: tauri::WebviewWindow) -> Result<Option<String>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        let download_dir = UserDirs::new()
+            .and_then(|dirs| dirs.download_dir().map(Path::to_path_buf))
+            .ok_or("Could not determine Downloads folder")?;
+        let path = unique_download_path(&download_dir, &pdf_file_name(&window));
+
+        export_pdf(&window, &path).await?;
+        info!("Saved PDF to {}", path.display());
+        Ok(Some(path.display().to_string()))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        window
+            .print()
+            .map_err(|e| format!("Failed to open print dialog: {}", e))?;
+        Ok(None)
+    }
+}
+
+/// Print the focused page from the menu or shortcut, showing the PDF once saved
+fn print_focused_page(app: &AppHandle) {
+    let Some(window) = focused_window(app).filter(|window| is_app_window(window.label())) else {
+        return;
+    };
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match print_to_pdf(window).await {
+            Ok(Some(path)) => reveal_in_file_manager(Path::new(&path)),
+            Ok(None) => {}
+            Err(e) => {
+                error!("{}", e);
+                message_dialog(&app, "Couldn't Save PDF", &e, MessageDialogKind::Error);
+            }
+        }
+    });
+}
+
+/// The id the OS screen capture API knows a window by. Must run on the main
+/// thread.
+fn native_window_id(window: &WebviewWindow) -> Result<u32, String> {
+    #[cfg(target_os = "macos")]
+    {
+        let ns_window = window
+            .ns_window()
+            .map_err(|e| format!("Failed to access window: {}", e))?
+            as *mut AnyObject;
+        let number: isize = unsafe { msg_send![ns_window, windowNumber] };
+        Ok(number as u32)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let hwnd = window
+            .hwnd()
+            .map_err(|e| format!("Failed to access window: {}", e))?;
+        Ok(hwnd.0 as usize as u32)
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        use gtk::prelude::*;
+
+        let gtk_window = window
+            .gtk_window()
+            .map_err(|e| format!("Failed to access window: {}", e))?;
+        let gdk_window = gtk_window
+            .window()
+            .ok_or("The window isn't on screen yet")?;
+        let x11_window = gdk_window
+            .downcast::<gdkx11::X11Window>()
+            .map_err(|_| "Screenshots are only supported in X11 sessions".to_string())?;
+        Ok(x11_window.xid() as u32)
+    }
+}
+
+/// Save a PNG of the calling window to Downloads and return where it was
+/// saved. The page doesn't get to pick the path, so it can't write elsewhere.
+#[tauri::command]
+async fn capture_window(window: tauri::WebviewWindow) -> Result<String, String> {
+    // A hidden or minimized window would capture as a blank image
+    if window.is_minimized().unwrap_or(false) {
+        return Err("Can't capture a minimized window; restore it first".to_string());
+    }
+    if !window.is_visible().unwrap_or(true) {
+        return Err("Can't capture a hidden window; show it first".to_string());
+    }
+
+    let download_dir = UserDirs::new()
+        .and_then(|dirs| dirs.download_dir().map(Path::to_path_buf))
+        .ok_or("Could not determine Downloads folder")?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let path = unique_download_path(&download_dir, &format!("Onyx Screenshot {}.png", timestamp));
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let target = window.clone();
+    window
+        .run_on_main_thread(move || {
+            let _ = sender.send(native_window_id(&target));
+        })
+        .map_err(|e| format!("Failed to access window: {}", e))?;
+
+    let saved_path = path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let id = receiver
+            .recv()
+            .map_err(|_| "Failed to access window: the window was closed".to_string())??;
+
+        let windows = xcap::Window::all().map_err(|e| format!("Failed to list windows: {}", e))?;
+        let target = windows
+            .into_iter()
+            .find(|candidate| candidate.id() == id)
+            .ok_or("Failed to capture window: it isn't visible to screen capture")?;
+        if target.is_minimized() {
+            return Err("Can't capture a minimized window; restore it first".to_string());
+        }
+
+        let image = target
+            .capture_image()
+            .map_err(|e| format!("Failed to capture window: {}", e))?;
+        image.save(&saved_path).map_err(|e| {
+            format!(
+                "Failed to save screenshot to {}: {}",
+                saved_path.display(),
+                e
+            )
+        })
+    })
+    .await
+    .map_err(|e| format!("Failed to capture window: {}", e))??;
+
+    info!("Saved screenshot to {}", path.display());
+    Ok(path.display().to_string())
+}
+
+/// Capture the focused window from the menu or shortcut, showing the image
+/// once saved
+fn capture_focused_window(app: &AppHandle) {
+    let Some(window) = focused_window(app).filter(|window| is_app_window(window.label())) else {
+        return;
+    };
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match capture_window(window).await {
+            Ok(path) => reveal_in_file_manager(Path::new(&path)),
+            Err(e) => {
+                error!("{}", e);
+                message_dialog(
+                    &app,
+                    "Couldn't Save Screenshot",
+                    &e,
+                    MessageDialogKind::Error,
+                );
+            }
+        }
+    });
+}
+
+/// The window the user is currently interacting with, falling back to main
+fn focused_window(app: &AppHandle) -> Option<WebviewWindow> {
+    app.webview_windows()
+        .into_values()
+        .find(|window| window.is_focused().unwrap_or(false))
+        .or_else(|| app.get_webview_window("main"))
+}
+
+/// Open the web inspector for a window, or close it if it's open. Only
+/// available in developer mode.
+#[tauri::command]
+fn toggle_devtools(
+    window: tauri::WebviewWindow,
+    state: tauri::State<ConfigState>,
+) -> Result<(), String> {
+    if !state.config.read().unwrap().devtools_enabled() {
+        return Err("Developer tools are disabled; set developer_mode in config.json".to_string());
+    }
+
+    if window.is_devtools_open() {
+        window.close_devtools();
+    } else {
+        window.open_devtools();
+    }
+    Ok(())
+}
+
+/// Restore the default size and center the window on a visible monitor
+fn reset_window_to_default(window: &WebviewWindow) -> tauri::Result<()> {
+    let _ = window.set_fullscreen(false);
+    let _ = window.unminimize();
+    window.unmaximize()?;
+    let sizes = window.state::<ConfigState>().window_sizes();
+    window.set_size(LogicalSize::new(sizes.width, sizes.height))?;
+
+    // An off-screen window may have no current monitor, so fall back to the
+    // primary one and position it explicitly rather than relying on center()
+    let monitor = match window.current_monitor()? {
+        Some(monitor) => Some(monitor),
+        None => window.primary_monitor()?,
+    };
+    if let Some(monitor) = monitor {
+        let window_size = window.outer_size()?;
+        let x = monitor.position().x
+            + (monitor.size().width as i32 - window_size.width as i32).max(0) / 2;
+        let y = monitor.position().y
+            + (monitor.size().height as i32 - window_size.height as i32).max(0) / 2;
+        window.set_position(PhysicalPosition::new(x, y))?;
+    }
+
+    window.show()?;
+    window.set_focus()?;
+
+    // Overwrite the persisted geometry so a restart doesn't restore the old position
+    let _ = window
+        .app_handle()
+        .save_window_state(StateFlags::SIZE | StateFlags::POSITION);
+
+    Ok(())
+}
+
+/// Keep a saved geometry reachable: returned unchanged if at least
+/// `MIN_VISIBLE_WINDOW_PART` of it overlaps one of `monitors`, otherwise
+/// shrunk to fit and centered on `primary`. Monitors are `(x, y, width,
+/// height)` in logical pixels.
+fn clamp_geometry(
+    geometry: WindowGeometry,
+    monitors: &[(f64, f64, f64, f64)],
+    primary: (f64, f64, f64, f64),
+) -> WindowGeometry {
+    let visible = monitors.iter().any(|&(x, y, width, height)| {
+        let overlap_x = (geometry.x + geometry.width).min(x + width) - geometry.x.max(x);
+        let overlap_y = (geometry.y + geometry.height).min(y + height) - geometry.y.max(y);
+        overlap_x >= MIN_VISIBLE_WINDOW_PART && overlap_y >= MIN_VISIBLE_WINDOW_PART
+    });
+    if visible {
+        return geometry;
+    }
+
+    let (x, y, width, height) = primary;
+    let fitted_width = geometry.width.min(width);
+    let fitted_height = geometry.height.min(height);
+    WindowGeometry {
+        x: x + (width - fitted_width) / 2.0,
+        y: y + (height - fitted_height) / 2.0,
+        width: fitted_width,
+        height: fitted_height,
+    }
+}
+
+/// A window's current geometry, unless it's minimized, maximized, or
+/// fullscreen and so doesn't reflect a layout worth restoring
+fn window_geometry(window: &WebviewWindow) -> Option<WindowGeometry> {
+    let resized = window.is_minimized().unwrap_or(false)
+        || window.is_maximized().unwrap_or(false)
+        || window.is_fullscreen().unwrap_or(false);
+    if resized {
+        return None;
+    }
+
+    let scale = window.scale_factor().ok()?;
+    let size = window.inner_size().ok()?.to_logical::<f64>(scale);
+    let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+    Some(WindowGeometry {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// Record a window's geometry under its server's profile in the in-memory
+/// config. Returns whether anything was recorded; the caller saves.
+fn remember_window_geometry(app: &AppHandle, window: &WebviewWindow) -> bool {
+    let Some(geometry) = window_geometry(window) else {
+        return false;
+    };
+    let server_url = window_server_url(app, window.label());
+
+    let state = app.state::<ConfigState>();
+    let mut config = state.config.write().unwrap();
+    let Some(profile) = config.profile_name_for(&server_url).map(str::to_string) else {
+        return false;
+    };
+    config.window_geometry.insert(profile, geometry);
+    true
+}
+
+/// Remember a closing window's geometry and write it to disk
+fn persist_window_geometry(app: &AppHandle, window: &WebviewWindow) {
+    let state = app.state::<ConfigState>();
+    // Before first-run setup there's no config file to write
+    if !*state.config_initialized.read().unwrap() || !remember_window_geometry(app, window) {
+        return;
+    }
+    if let Err(e) = save_config(&state.config.read().unwrap()) {
+        error!("Failed to save window geometry: {}", e);
+    }
+}
+
+/// The geometry last saved for `server_url`'s profile, moved onto the primary
+/// monitor if it's no longer on any screen (e.g. a monitor was unplugged)
+fn saved_geometry_for(app: &AppHandle, server_url: &str) -> Option<WindowGeometry> {
+    let geometry = {
+        let state = app.state::<ConfigState>();
+        let config = state.config.read().unwrap();
+        let profile = config.profile_name_for(server_url)?;
+        *config.window_geometry.get(profile)?
+    };
+
+    let bounds = |monitor: &tauri::Monitor| {
+        let scale = monitor.scale_factor();
+        let position = monitor.position().to_logical::<f64>(scale);
+        let size = monitor.size().to_logical::<f64>(scale);
+        (position.x, position.y, size.width, size.height)
+    };
+    let monitors: Vec<_> = app
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(bounds)
+        .collect();
+    let primary = app
+        .primary_monitor()
+        .ok()
+        .flatten()
+        .map(|monitor| bounds(&monitor))
+        .or_else(|| monitors.first().copied())?;
+
+    Some(clamp_geometry(geometry, &monitors, primary))
+}
+
+/// Move and resize a window to a saved geometry
+fn apply_window_geometry(window: &WebviewWindow, geometry: WindowGeometry) -> tauri::Result<()> {
+    window.set_size(LogicalSize::new(geometry.width, geometry.height))?;
+    window.set_position(LogicalPosition::new(geometry.x, geometry.y))
+}
+
+/// Size and position for a new window on `server_url`: the geometry last saved
+/// for its profile if there is one, otherwise the configured size, cascaded.
+/// The saved spot goes to the profile's first window; more cascade from there.
+fn new_window_placement(
+    app: &AppHandle,
+    state: &ConfigState,
+    server_url: &str,
+) -> (WindowSizes, Option<LogicalPosition<f64>>) {
+    let saved = saved_geometry_for(app, server_url);
+    let sizes = match saved {
+        Some(geometry) => WindowSizes {
+            width: geometry.width,
+            height: geometry.height,
+            ..state.window_sizes()
+        },
+        None => state.window_sizes(),
+    };
+
+    let server_open = app
+        .webview_windows()
+        .keys()
+        .any(|label| is_app_window(label) && window_server_url(app, label) == server_url);
+    let position = match saved {
+        Some(geometry) if !server_open => Some(LogicalPosition::new(geometry.x, geometry.y)),
+        _ => next_cascade_position(app, &sizes),
+    };
+    (sizes, position)
+}
+
+/// Where the `index`th cascaded window goes, relative to the monitor's
+/// top-left corner: each step moves `WINDOW_CASCADE_STEP` right and down from
+/// the centered position, starting over before the window would run off the
+/// bottom-right edge. All values are logical pixels.
+fn cascade_offset(index: u32, monitor: (f64, f64), window: (f64, f64)) -> (f64, f64) {
+    let base_x = ((monitor.0 - window.0) / 2.0).max(0.0);
+    let base_y = ((monitor.1 - window.1) / 2.0).max(0.0);
+    let room = (monitor.0 - window.0 - base_x).min(monitor.1 - window.1 - base_y);
+    let steps = (room / WINDOW_CASCADE_STEP).floor().max(0.0) as u32 + 1;
+    let offset = f64::from(index % steps) * WINDOW_CASCADE_STEP;
+    (base_x + offset, base_y + offset)
+}
+
+/// Logical position for a new window, cascaded from the others so it doesn't
+/// land exactly on top of them. The main and restored windows keep their
+/// saved positions and aren't cascaded.
+fn next_cascade_position(app: &AppHandle, sizes: &WindowSizes) -> Option<LogicalPosition<f64>> {
+    let monitor = focused_window(app)
+        .and_then(|window| window.current_monitor().ok().flatten())
+        .or_else(|| app.primary_monitor().ok().flatten())?;
+    let scale = monitor.scale_factor();
+    let origin = monitor.position().to_logical::<f64>(scale);
+    let size = monitor.size().to_logical::<f64>(scale);
+
+    // The main window sits at the centered spot, so extra windows start one step in
+    let open_windows = app
+        .webview_windows()
+        .keys()
+        .filter(|label| label.starts_with("onyx-"))
+        .count() as u32;
+    let (x, y) = cascade_offset(
+        open_windows + 1,
+        (size.width, size.height),
+        (sizes.width, sizes.height),
+    );
+    Some(LogicalPosition::new(origin.x + x, origin.y + y))
+}
+
+/// Script that sends the page to `url`. The URL is JSON-encoded, so quotes,
+/// backslashes, and line breaks in it can't end the string literal early.
+fn navigation_script(url: &str) -> String {
+    format!(
+        "window.location.href = {};",
+        serde_json::Value::String(url.to_string())
+    )
+}
+
+fn trigger_new_chat(app: &AppHandle) {
+    let state = app.state::<ConfigState>();
+    let server_url = state.server_url();
+
+    if let Some(window) = app.get_webview_window("main") {
+        let url = format!("{}/chat", server_url);
+        let _ = window.eval(&navigation_script(&url));
+    }
+}
+
+fn trigger_new_window(app: &AppHandle) {
+    trigger_new_window_at(app, "");
+}
+
+/// URL for a new window at `path` on `server_url`, falling back to the
+/// default server when that doesn't resolve (e.g. after a hand edit left the
+/// scheme out of the configured URL)
+fn new_window_url(server_url: &str, path: &str) -> Option<Url> {
+    match server_path_url(server_url, path) {
+        Ok(url) => Some(url),
+        Err(e) => {
+            let fallback = default_server_url();
+            warn!("{}; falling back to {}", e, fallback);
+            parse_server_url(&fallback).ok()
+        }
+    }
+}
+
+/// Open a new window on the active server at `path` (e.g. `/chat`), or at
+/// the server root when `path` is empty
+fn trigger_new_window_at(app: &AppHandle, path: &str) {
+    let state = app.state::<ConfigState>();
+    let server_url = state.server_url();
+    let zoom_level = state.zoom_level();
+    let user_agent = state.user_agent();
+    let proxy_url = state.proxy_url();
+    let native_drag_drop = state.native_drag_drop();
+    #[cfg(target_os = "linux")]
+    let background_color = state.background_color();
+    let transparent = !state.vibrancy_disabled();
+    let (sizes, position) = new_window_placement(app, &state, &server_url);
+    let title = state.window_title();
+    let always_on_top = state.always_on_top();
+    let handle = app.clone();
+
+    let Some(server_url) = new_window_url(&server_url, path) else {
+        return;
+    };
+
+    tauri::async_runtime::spawn(async move {
+        let window_label = format!("onyx-{}", uuid::Uuid::new_v4());
+        let builder =
+            WebviewWindowBuilder::new(&handle, &window_label, WebviewUrl::External(server_url))
+                .title(&title)
+                .inner_size(sizes.width, sizes.height)
+                .min_inner_size(sizes.min_width, sizes.min_height)
+                .transparent(transparent);
+        let builder = match position {
+            Some(position) => builder.position(position.x, position.y),
+            None => builder,
+        };
+
+        #[cfg(target_os = "macos")]
+        let builder = builder
+            .title_bar_style(tauri::TitleBarStyle::Overlay)
+            .hidden_title(true);
+
+        #[cfg(target_os = "linux")]
+        let builder =
+            builder.background_color(background_color.unwrap_or(DEFAULT_BACKGROUND_COLOR));
+
+        let builder = apply_drag_drop(builder, native_drag_drop);
+        let builder = apply_download_handler(apply_proxy(
+            apply_user_agent(builder, user_agent.as_deref()),
+            proxy_url,
+        ));
+
+        if let Ok(window) = builder.build() {
+            let _ = window.set_zoom(zoom_level);
+
+            apply_window_theme(&window);
+            if always_on_top {
+                let _ = apply_always_on_top(&handle, &window, true);
+            }
+
+            let _ = window.set_focus();
+        }
+    });
+}
+
+/// Path of a chat session on the Onyx web app
+fn chat_path(chat_id: &str) -> String {
+    format!("/chat?chatId={}", chat_id)
+}
+
+/// Whether `chat_id` looks like an Onyx chat ID: non-empty and only letters,
+/// digits, `-`, and `_`, so it can't break out of a URL or script
+fn is_valid_chat_id(chat_id: &str) -> bool {
+    !chat_id.is_empty()
+        && chat_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Where an `onyx://` deep link points
+#[derive(Debug, PartialEq)]
+enum DeepLink {
+    /// `onyx://chat/new`
+    NewChat,
+    /// `onyx://chat/<id>`
+    Chat(String),
+    /// `onyx://go/<path>[?<query>]`, any page on the active server
+    Go(String),
+    /// `onyx://auth/callback?<query>`, an identity provider sending the user
+    /// back after SSO sign-in
+    SsoCallback(Option<String>),
+}
+
+/// Parse an `onyx://chat/new`, `onyx://chat/<id>`, `onyx://go/<path>`, or
+/// `onyx://auth/callback` deep link
+fn parse_deep_link(raw: &str) -> Result<DeepLink, String> {
+    let url = Url::parse(raw).map_err(|e| format!("Invalid deep link '{}': {}", raw, e))?;
+    if url.scheme() != DEEP_LINK_SCHEME {
+        return Err(format!("Unsupported deep link scheme in '{}'", raw));
+    }
+
+    match (url.host_str(), url.path().trim_matches('/')) {
+        (Some("chat"), "new") => Ok(DeepLink::NewChat),
+        (Some("chat"), chat_id) if is_valid_chat_id(chat_id) => {
+            Ok(DeepLink::Chat(chat_id.to_string()))
+        }
+        (Some("auth"), "callback") => Ok(DeepLink::SsoCallback(url.query().map(str::to_string))),
+        // Resolved with `server_path_url`, which keeps it on the server
+        (Some("go"), path) => Ok(DeepLink::Go(match url.query() {
+            Some(query) => format!("/{}?{}", path, query),
+            None => format!("/{}", path),
+        })),
+        _ => Err(format!("Unrecognized deep link '{}'", raw)),
+    }
+}
+
+/// Bring the main window forward and open what a deep link points at
+fn handle_deep_link(app: &AppHandle, raw: &str) {
+    match parse_deep_link(raw) {
+        Ok(DeepLink::NewChat) => open_path(app, "/chat"),
+        Ok(DeepLink::Chat(chat_id)) => show_chat(app, &chat_id),
+        Ok(DeepLink::Go(path)) => open_path(app, &path),
+        Ok(DeepLink::SsoCallback(query)) => {
+            let callback_path = app
+                .state::<ConfigState>()
+                .config
+                .read()
+                .unwrap()
+                .sso_callback_path
+                .clone();
+            let path = match query {
+                Some(query) => format!("{}?{}", callback_path, query),
+                None => callback_path,
+            };
+            open_path(app, &path);
+        }
+        Err(e) => {
+            warn!("Ignoring deep link: {}", e);
+            focus_main_window(app);
+        }
+    }
+}
+
+/// Bring the main window forward and navigate it to a chat session
+fn show_chat(app: &AppHandle, chat_id: &str) {
+    open_path(app, &chat_path(chat_id));
+}
+
+/// Bring the main window forward and open the chat with `chat_id`, which must
+/// pass `is_valid_chat_id`
+#[tauri::command]
+fn open_chat(app: AppHandle, chat_id: String) -> Result<(), String> {
+    if !is_valid_chat_id(&chat_id) {
+        return Err(format!("Invalid chat ID '{}'", chat_id));
+    }
+    show_chat(&app, &chat_id);
+    Ok(())
+}
+
+/// Bring the app forward (see `summon_target_window`) and navigate it to
+/// `path` on the configured server, opening a window there if none exists
+fn open_path(app: &AppHandle, path: &str) {
+    let Some(window) = summon_target_window(app) else {
+        trigger_new_window_at(app, path);
+        return;
+    };
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+
+    let server_url = app.state::<ConfigState>().server_url();
+    let target = match server_path_url(&server_url, path) {
+        Ok(url) => url,
+        Err(e) => {
+            error!("Failed to open {}: {}", path, e);
+            return;
+        }
+    };
+    let _ = window.navigate(target);
+}
+
+fn open_docs() {
+    open_url("https://docs.onyx.app");
+}
+
+/// Open `window`'s current page in the system browser, falling back to the
+/// configured server when the URL can't be read or isn't a web page
+fn open_page_in_browser(app: &AppHandle, window: &WebviewWindow) {
+    let url = match window.url() {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url.to_string(),
+        Ok(_) => app.state::<ConfigState>().server_url(),
+        Err(e) => {
+            error!("Failed to read window URL: {}", e);
+            app.state::<ConfigState>().server_url()
+        }
+    };
+    open_url(&url);
+}
+
+/// Open a URL with the platform's default handler
+fn open_url(url: &str) {
+    #[cfg(target_os = "macos")]
+    {
+        let _ = Command::new("open").arg(url).status();
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let _ = Command::new("xdg-open").arg(url).status();
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let _ = Command::new("rundll32")
+            .arg("url.dll,FileProtocolHandler")
+            .arg(url)
+            .status();
+    }
+}
+
+/// Whether `target` belongs to the app itself or the configured server.
+/// Non-web schemes (tauri://, about:, blob:, data:) always stay in-app.
+fn is_in_app_url(target: &Url, server_url: &str) -> bool {
+    if target.scheme() != "http" && target.scheme() != "https" {
+        return true;
+    }
+
+    // Bundled pages are served from http://tauri.localhost on Windows
+    if target.host_str() == Some("tauri.localhost") {
+        return true;
+    }
+
+    match Url::parse(server_url) {
+        Ok(server) => hosts_match(target, &server),
+        Err(_) => true,
+    }
+}
+
+/// Windows partway through SSO sign-in, by label, with the identity
+/// provider page that started it. Until they land back on the server, they
+/// may visit that provider's pages and no others.
+struct SsoState {
+    windows: Mutex<HashMap<String, Url>>,
+}
+
+/// Whether `url` is one of the server's SSO callbacks
+fn is_sso_callback(url: &Url, server: &Url, callback_path: &str) -> bool {
+    let path = url.path().trim_end_matches('/');
+    hosts_match(url, server)
+        && (path == callback_path.trim_end_matches('/') || SSO_CALLBACK_PATHS.contains(&path))
+}
+
+/// Whether navigating from `current` to the off-server `target` starts SSO
+/// sign-in: either the target is an authorization request whose
+/// `redirect_uri` is one of the server's callbacks, or the window is leaving
+/// the server's sign-in pages (SAML requests don't carry a readable callback)
+fn starts_sso(target: &Url, current: Option<&Url>, server: &Url, callback_path: &str) -> bool {
+    let redirects_to_callback = target
+        .query_pairs()
+        .filter(|(key, _)| key == "redirect_uri")
+        .filter_map(|(_, value)| Url::parse(&value).ok())
+        .any(|redirect| is_sso_callback(&redirect, server, callback_path));
+
+    let leaving_sign_in = current.is_some_and(|current| {
+        hosts_match(current, server) && current.path().starts_with("/auth/")
+    });
+
+    redirects_to_callback || leaving_sign_in
+}
+
+/// Decide whether an off-server navigation stays in the window because it's
+/// part of SSO sign-in, i.e. on the identity provider's host. Landing back on
+/// the server ends the flow.
+fn allow_sso_navigation(webview: &Webview, target: &Url, server_url: &str) -> bool {
+    let Ok(server) = Url::parse(server_url) else {
+        return false;
+    };
+    let sso = webview.state::<SsoState>();
+    let mut windows = sso.windows.lock().unwrap();
+
+    if hosts_match(target, &server) {
+        windows.remove(webview.label());
+        return false;
+    }
+    if let Some(provider) = windows.get(webview.label()) {
+        return hosts_match(target, provider);
+    }
+
+    let callback_path = webview
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .sso_callback_path
+        .clone();
+    let current = webview.url().ok();
+    if starts_sso(target, current.as_ref(), &server, &callback_path) {
+        info!(
+            "Keeping SSO sign-in at {} in the app",
+            target.host_str().unwrap_or_default()
+        );
+        windows.insert(webview.label().to_string(), target.clone());
+        return true;
+    }
+
+    false
+}
+
+/// Compare the hosts of two URLs, ignoring case and a leading `www.`
+fn hosts_match(a: &Url, b: &Url) -> bool {
+    fn normalized(url: &Url) -> Option<String> {
+        url.host_str()
+            .map(|host| host.trim_start_matches("www.").to_ascii_lowercase())
+    }
+
+    match (normalized(a), normalized(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Build the dedicated settings window, or focus it if it's already open
+fn show_settings_window(app: &AppHandle) -> tauri::Result<()> {
+    if let Some(window) = app.get_webview_window(SETTINGS_WINDOW_LABEL) {
+        let _ = window.unminimize();
+        window.show()?;
+        return window.set_focus();
+    }
+
+    WebviewWindowBuilder::new(
+        app,
+        SETTINGS_WINDOW_LABEL,
+        WebviewUrl::App(SETTINGS_PAGE.into()),
+    )
+    .title("Settings")
+    .inner_size(480.0, 620.0)
+    .resizable(false)
+    .maximizable(false)
+    .minimizable(false)
+    .always_on_top(true)
+    .center()
+    .build()?
+    .set_focus()
+}
+
+/// Show the floating quick-chat window, building it on first use. It hides
+/// itself again when it loses focus.
+fn show_quick_chat_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window(QUICK_CHAT_WINDOW_LABEL) {
+        let _ = window.center();
+        let _ = window.show();
+        let _ = window.set_focus();
+        return;
+    }
+
+    let state = app.state::<ConfigState>();
+    let url = match parse_server_url(&format!("{}/chat", state.server_url())) {
+        Ok(url) => url,
+        Err(e) => {
+            error!("Failed to open quick chat: {}", e);
+            return;
+        }
+    };
+
+    let builder =
+        WebviewWindowBuilder::new(app, QUICK_CHAT_WINDOW_LABEL, WebviewUrl::External(url))
+            .title("Quick Chat")
+            .inner_size(QUICK_CHAT_WINDOW_WIDTH, QUICK_CHAT_WINDOW_HEIGHT)
+            .decorations(false)
+            .always_on_top(true)
+            .skip_taskbar(true)
+            .resizable(false)
+            .center();
+    let builder = apply_drag_drop(builder, state.native_drag_drop());
+    let builder = apply_download_handler(apply_proxy(
+        apply_user_agent(builder, state.user_agent().as_deref()),
+        state.proxy_url(),
+    ));
+
+    match builder.build() {
+        Ok(window) => {
+            let _ = window.set_zoom(state.zoom_level());
+            let _ = window.set_focus();
+        }
+        Err(e) => error!("Failed to create quick chat window: {}", e),
+    }
+}
+
+/// Point every window still showing `previous_url`'s server at `new_url`.
+/// Windows elsewhere (the bundled pages, other sites) are left alone.
+fn move_windows_to_server(app: &AppHandle, previous_url: &str, new_url: &str) {
+    let (Ok(previous), Ok(new)) = (Url::parse(previous_url), parse_server_url(new_url)) else {
+        return;
+    };
+    if previous.origin() == new.origin() {
+        return;
+    }
+
+    for window in app.webview_windows().values() {
+        if is_pinned_to_server(app, window.label()) {
+            continue;
+        }
+
+        let on_previous_server = window
+            .url()
+            .map(|current| hosts_match(&current, &previous))
+            .unwrap_or(false);
+        if on_previous_server {
+            let _ = window.navigate(new.clone());
+        }
+    }
+}
+
+fn open_settings(app: &AppHandle) {
+    let handle = app.clone();
+
+    // Build off the event thread; creating windows synchronously can deadlock on Windows
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = show_settings_window(&handle) {
+            error!("Failed to open settings window: {}", e);
+        }
+    });
+}
+
+// ============================================================================
+// Tauri Commands
+// ============================================================================
+
+/// Get the server URL of the calling window: the active server unless the
+/// window was opened on another one
+#[tauri::command]
+fn get_server_url(window: tauri::WebviewWindow) -> String {
+    window_server_url(window.app_handle(), window.label())
+}
+
+#[derive(Serialize)]
+struct BootstrapState {
+    server_url: String,
+    config_exists: bool,
+}
+
+/// Get the server URL plus whether a config file exists
+#[tauri::command]
+fn get_bootstrap_state(state: tauri::State<ConfigState>) -> BootstrapState {
+    let server_url = state.server_url();
+    let config_initialized = *state.config_initialized.read().unwrap();
+    let config_exists = config_initialized && get_config_path().exists();
+
+    BootstrapState {
+        server_url,
+        config_exists,
+    }
+}
+
+/// Set a new server URL and save to config. A new URL must answer its
+/// health check first (within `HEALTH_CHECK_TIMEOUT`), so a typo is reported
+/// instead of leaving a blank window; on failure the previous URL is kept.
+#[tauri::command]
+async fn set_server_url(
+    app: AppHandle,
+    state: tauri::State<'_, ConfigState>,
+    url: String,
+) -> Result<String, String> {
+    let url = validate_server_url(&url)?;
+    ensure_unmanaged("server_url", url.clone().into())?;
+    let previous_url = state.server_url();
+
+    if url != previous_url {
+        let (client, basic_auth) = {
+            let config = state.config.read().unwrap();
+            (http_client(&config)?, config.basic_auth_for(&url))
+        };
+        ping_server(&client, &url, basic_auth).await?;
+    }
+
+    {
+        let mut config = state.config.write().unwrap();
+        config.set_active_server_url(&url);
+        config.remember_server(&url);
+        // An explicit choice replaces any session override from the environment
+        config.server_url_override = None;
+        save_config(&config)?;
+        *state.config_initialized.write().unwrap() = true;
+        emit_config_changed(&app, &config);
+    }
+
+    move_windows_to_server(&app, &previous_url, &url);
+    refresh_tray_menu(&app);
+
+    Ok(url)
+}
+
+/// Get previously used server URLs for quick switching, newest first. The
+/// active server is left out.
+#[tauri::command]
+fn get_recent_servers(state: tauri::State<ConfigState>) -> Vec<String> {
+    state.config.read().unwrap().recent_servers()
+}
+
+/// Switch to the server profile at `index` and move open windows over to it.
+/// Returns the new server URL.
+#[tauri::command]
+fn switch_server(app: AppHandle, index: usize) -> Result<String, String> {
+    let state = app.state::<ConfigState>();
+    let previous_url = state.server_url();
+    let main = app.get_webview_window("main");
+    // Saved along with the switch below
+    if let Some(main) = &main {
+        remember_window_geometry(&app, main);
+    }
+
+    let url = {
+        let mut config = state.config.write().unwrap();
+        if let Some(profile) = config.servers.get(index) {
+            ensure_unmanaged("server_url", profile.url.clone().into())?;
+        }
+        // Only take the switch once it's on disk
+        let mut updated = config.clone();
+        updated.activate_server(index)?;
+        updated.server_url_override = None;
+        save_config(&updated)?;
+        *config = updated;
+        *state.config_initialized.write().unwrap() = true;
+        emit_config_changed(&app, &config);
+        config.server_url.clone()
+    };
+
+    move_windows_to_server(&app, &previous_url, &url);
+    // The main window takes on the layout last used with the new profile
+    if let (Some(main), Some(geometry)) = (main, saved_geometry_for(&app, &url)) {
+        if let Err(e) = apply_window_geometry(&main, geometry) {
+            warn!("Failed to restore main window geometry: {}", e);
+        }
+    }
+    refresh_tray_menu(&app);
+
+    Ok(url)
+}
+
+/// A server profile, as listed for profile pickers
+#[derive(Debug, Clone, Serialize)]
+struct ServerProfileInfo {
+    name: String,
+    url: String,
+    active: bool,
+}
+
+/// List the server profiles in config order, marking the active one
+#[tauri::command]
+fn list_profiles(state: tauri::State<ConfigState>) -> Vec<ServerProfileInfo> {
+    let config = state.config.read().unwrap();
+    config
+        .servers
+        .iter()
+        .enumerate()
+        .map(|(index, profile)| ServerProfileInfo {
+            name: profile.name.clone(),
+            url: profile.url.clone(),
+            active: index == config.active_server,
+        })
+        .collect()
+}
+
+/// Save the server profile list after `edit` succeeds on it
+fn edit_profiles(
+    app: &AppHandle,
+    edit: impl FnOnce(&mut AppConfig) -> Result<(), String>,
+) -> Result<Vec<ServerProfileInfo>, String> {
+    let state = app.state::<ConfigState>();
+    {
+        let mut config = state.config.write().unwrap();
+        let mut updated = config.clone();
+        edit(&mut updated)?;
+        let servers = serde_json::to_value(&updated.servers)
+            .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+        ensure_unmanaged("servers", servers)?;
+
+        save_config(&updated)?;
+        *config = updated;
+        *state.config_initialized.write().unwrap() = true;
+        emit_config_changed(app, &config);
+    }
+
+    refresh_tray_menu(app);
+    Ok(list_profiles(state))
+}
+
+/// Add a named server profile. Returns the updated profile list.
+#[tauri::command]
+fn add_profile(
+    app: AppHandle,
+    name: String,
+    server_url: String,
+) -> Result<Vec<ServerProfileInfo>, String> {
+    edit_profiles(&app, |config| config.add_server_profile(&name, &server_url))
+}
+
+/// Remove a server profile other than the active one. Returns the updated
+/// profile list.
+#[tauri::command]
+fn remove_profile(app: AppHandle, name: String) -> Result<Vec<ServerProfileInfo>, String> {
+    edit_profiles(&app, |config| config.remove_server_profile(&name))
+}
+
+/// Switch to the server profile called `name`; see `switch_server`
+#[tauri::command]
+fn switch_profile(app: AppHandle, name: String) -> Result<String, String> {
+    let index = app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .server_profile_index(&name)
+        .ok_or_else(|| format!("No profile named '{}'", name))?;
+    switch_server(app, index)
+}
+
+/// Check that the server (the configured one, or `url` if given) answers its
+/// health endpoint. Returns the round-trip latency in milliseconds.
+#[tauri::command]
+async fn check_server_reachable(
+    state: tauri::State<'_, ConfigState>,
+    url: Option<String>,
+) -> Result<u64, String> {
+    let server_url = match url {
+        Some(url) => validate_server_url(&url)?,
+        None => state.server_url(),
+    };
+    let (client, basic_auth) = {
+        let config = state.config.read().unwrap();
+        (http_client(&config)?, config.basic_auth_for(&server_url))
+    };
+
+    ping_server(&client, &server_url, basic_auth).await
+}
+
+/// TLS and proxy settings a connection-check client is built with
+#[derive(PartialEq)]
+struct HttpClientSettings {
+    custom_ca_path: Option<String>,
+    danger_accept_invalid_certs: bool,
+    proxy_url: Option<Url>,
+}
+
+/// Connection-check client, with the settings it was built from
+static HTTP_CLIENT: Mutex<Option<(HttpClientSettings, reqwest::Client)>> = Mutex::new(None);
+
+/// HTTP client that honors the config's TLS settings, so connection checks
+/// agree with what the webview accepts. It's built once and reused until
+/// those settings change.
+fn http_client(config: &AppConfig) -> Result<reqwest::Client, String> {
+    let settings = HttpClientSettings {
+        custom_ca_path: config.custom_ca_path.clone(),
+        danger_accept_invalid_certs: config.danger_accept_invalid_certs,
+        proxy_url: config.proxy_url(),
+    };
+
+    let mut cached = HTTP_CLIENT.lock().unwrap();
+    if let Some((built_with, client)) = &*cached {
+        if *built_with == settings {
+            return Ok(client.clone());
+        }
+    }
+
+    let client = build_http_client(&settings)?;
+    *cached = Some((settings, client.clone()));
+    Ok(client)
+}
+
+fn build_http_client(settings: &HttpClientSettings) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().timeout(HEALTH_CHECK_TIMEOUT);
+
+    if let Some(ca_path) = &settings.custom_ca_path {
+        let pem = fs::read(ca_path)
+            .map_err(|e| format!("Failed to read CA certificates from {}: {}", ca_path, e))?;
+        let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|e| format!("Invalid CA certificates in {}: {}", ca_path, e))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    if settings.danger_accept_invalid_certs {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    if let Some(proxy_url) = &settings.proxy_url {
+        let proxy = reqwest::Proxy::all(proxy_url.as_str())
+            .map_err(|e| format!("Failed to configure proxy: {}", e))?;
+        builder = builder.proxy(proxy);
+    }
+
+    builder
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+/// GET the server's health endpoint, returning the round-trip latency in milliseconds
+async fn ping_server(
+    client: &reqwest::Client,
+    server_url: &str,
+    basic_auth: Option<BasicAuth>,
+) -> Result<u64, String> {
+    let health_url = format!("{}{}", server_url, HEALTH_CHECK_PATH);
+    let mut request = client.get(&health_url);
+    if let Some(auth) = basic_auth {
+        request = request.basic_auth(auth.user, Some(auth.pass));
+    }
+
+    let started = Instant::now();
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() {
+            format!("Timed out reaching {}", server_url)
+        } else {
+            format!("Could not reach {}: {}", server_url, e)
+        }
+    })?;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "{} responded with status {}",
+            server_url,
+            response.status()
+        ));
+    }
+
+    Ok(latency_ms)
+}
+
+/// Check for a newer release. With `interactive`, every outcome (up to date,
+/// errors) is reported in a dialog; otherwise only an available update is.
+/// An update is downloaded and installed only after the user confirms.
+async fn run_update_check(app: &AppHandle, interactive: bool) -> Result<(), String> {
+    let (Some(pubkey), Some(endpoint)) = (UPDATER_PUBKEY, UPDATER_ENDPOINT) else {
+        let message = "Updates aren't configured for this build.";
+        if interactive {
+            message_dialog(app, "Check for Updates", message, MessageDialogKind::Info);
+        }
+        return Err(message.to_string());
+    };
+
+    let check = async {
+        let endpoint = Url::parse(endpoint).map_err(|e| e.to_string())?;
+        app.updater_builder()
+            .pubkey(pubkey)
+            .endpoints(vec![endpoint])
+            .and_then(|builder| builder.build())
+            .map_err(|e| e.to_string())?
+            .check()
+            .await
+            .map_err(|e| e.to_string())
+    };
+
+    let update = match check.await {
+        Ok(Some(update)) => update,
+        Ok(None) => {
+            if interactive {
+                message_dialog(
+                    app,
+                    "Check for Updates",
+                    &format!(
+                        "You're up to date. Onyx {} is the latest version.",
+                        app.package_info().version
+                    ),
+                    MessageDialogKind::Info,
+                );
+            }
+            return Ok(());
+        }
+        Err(e) => {
+            if interactive {
+                message_dialog(
+                    app,
+                    "Check for Updates",
+                    &format!("Couldn't check for updates:\n\n{}", e),
+                    MessageDialogKind::Error,
+                );
+            }
+            return Err(format!("Failed to check for updates: {}", e));
+        }
+    };
+
+    let mut message = format!(
+        "Onyx {} is available. You have {}.",
+        update.version, update.current_version
+    );
+    if let Some(notes) = update
+        .body
+        .as_deref()
+        .filter(|notes| !notes.trim().is_empty())
+    {
+        message.push_str("\n\n");
+        message.push_str(notes.trim());
+    }
+    let install = confirm_dialog(
+        app,
+        "Update Available",
+        &message,
+        MessageDialogKind::Info,
+        "Install",
+    )
+    .await?;
+    if !install {
+        return Ok(());
+    }
+
+    info!("Installing Onyx {}", update.version);
+    if let Err(e) = update.download_and_install(|_, _| {}, || {}).await {
+        message_dialog(
+            app,
+            "Update Failed",
+            &format!("Couldn't install the update:\n\n{}", e),
+            MessageDialogKind::Error,
+        );
+        return Err(format!("Failed to install update: {}", e));
+    }
+
+    let restart = confirm_dialog(
+        app,
+        "Update Installed",
+        "Restart Onyx now to finish updating?",
+        MessageDialogKind::Info,
+        "Restart",
+    )
+    .await?;
+    if restart {
+        flush_app_state(app);
+        app.restart();
+    }
+
+    Ok(())
+}
+
+/// Check for updates, reporting the result in native dialogs
+#[tauri::command]
+async fn check_for_updates(app: AppHandle) -> Result<(), String> {
+    run_update_check(&app, true).await
+}
+
+/// Get the config file path (so users know where to edit)
+#[tauri::command]
+fn get_config_path_cmd() -> Result<String, String> {
+    Ok(get_config_path().to_string_lossy().to_string())
+}
+
+/// Open the config file in the default editor
+#[tauri::command]
+fn open_config_file() -> Result<(), String> {
+    let config_path = get_config_path();
+
+    // Ensure config exists
+    if !config_path.exists() {
+        save_config(&AppConfig::default())?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg("-t")
+            .arg(&config_path)
+            .spawn()
+            .map_err(|e| format!("Failed to open config: {}", e))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&config_path)
+            .spawn()
+            .map_err(|e| format!("Failed to open config: {}", e))?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("notepad")
+            .arg(&config_path)
+            .spawn()
+            .map_err(|e| format!("Failed to open config: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Open the config directory in file manager
+#[tauri::command]
+fn open_config_directory() -> Result<(), String> {
+    let config_dir = resolve_config_dir();
+
+    // Ensure directory exists
+    fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
+
+    open_directory(&config_dir)
+}
+
+/// Open the log directory in file manager
+#[tauri::command]
+fn open_logs_directory() -> Result<(), String> {
+    let log_dir = get_log_dir();
+    fs::create_dir_all(&log_dir).map_err(|e| format!("Failed to create log dir: {}", e))?;
+
+    open_directory(&log_dir)
+}
+
+/// Version and platform details for the About dialog and bug reports
+#[derive(Debug, Clone, Serialize)]
+struct AppInfo {
+    version: String,
+    tauri_version: String,
+    os: String,
+    arch: String,
+    config_path: String,
+}
+
+impl AppInfo {
+    /// Plain-text summary, one detail per line, ready to paste into an issue
+    fn summary(&self) -> String {
+        format!(
+            "Onyx {}\nTauri {}\nPlatform: {} ({})\nConfig: {}",
+            self.version, self.tauri_version, self.os, self.arch, self.config_path
+        )
+    }
+}
+
+/// Get the app version, platform, and config location
+#[tauri::command]
+fn get_app_info(app: AppHandle) -> AppInfo {
+    AppInfo {
+        version: app.package_info().version.to_string(),
+        tauri_version: tauri::VERSION.to_string(),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        config_path: get_config_path().display().to_string(),
+    }
+}
+
+fn show_about_dialog(app: &AppHandle) {
+    let info = get_app_info(app.clone());
+    message_dialog(app, "About Onyx", &info.summary(), MessageDialogKind::Info);
+}
+
+/// Open a directory in the platform's file manager
+fn open_directory(dir: &Path) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(dir)
+            .spawn()
+            .map_err(|e| format!("Failed to open directory: {}", e))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(dir)
+            .spawn()
+            .map_err(|e| format!("Failed to open directory: {}", e))?;
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("explorer")
+            .arg(dir)
+            .spawn()
+            .map_err(|e| format!("Failed to open directory: {}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Navigate to a specific path on the configured server
+#[tauri::command]
+fn navigate_to(window: tauri::WebviewWindow, path: &str) -> Result<(), String> {
+    let base_url = window_server_url(window.app_handle(), window.label());
+    let url = server_path_url(&base_url, path)?;
+    window.navigate(url).map_err(|e| e.to_string())
+}
+
+/// Resolve `path` against `current` the way a link on that page would, so
+/// `settings` from `/chat/abc` gives `/chat/settings`, `../settings` gives
+/// `/settings`, and `/settings` is server-absolute. Only http(s) results on
+/// the current page's origin are allowed, so `//evil.example` can't leave it.
+fn resolve_relative_url(current: &Url, path: &str) -> Result<Url, String> {
+    let url = current
+        .join(path)
+        .map_err(|e| format!("Invalid path {}: {}", path, e))?;
+    if !matches!(url.scheme(), "http" | "https") || url.origin() != current.origin() {
+        return Err(format!("Refusing to navigate to {}", url));
+    }
+    Ok(url)
+}
+
+/// Resolve `path` (e.g. `/chat`) on `server_url`, refusing anything that
+/// would leave the server, so `.evil.com/x` stays a path on it rather than
+/// becoming another host
+fn server_path_url(server_url: &str, path: &str) -> Result<Url, String> {
+    resolve_relative_url(&parse_server_url(server_url)?, path)
+}
+
+/// Navigate to `path` relative to the window's current page rather than the
+/// server root
+#[tauri::command]
+fn navigate_relative(window: tauri::WebviewWindow, path: &str) -> Result<(), String> {
+    let current = window
+        .url()
+        .map_err(|e| format!("Failed to get current URL: {}", e))?;
+    let url = resolve_relative_url(&current, path)?;
+    window.navigate(url).map_err(|e| e.to_string())
+}
+
+/// Reload the current page
+#[tauri::command]
+fn reload_page(window: tauri::WebviewWindow) {
+    let _ = window.eval("window.location.reload()");
+}
+
+// Drops the page's Cache Storage and reloads under a fresh URL, for webviews
+// without a native reload that bypasses the cache
+#[cfg(target_os = "windows")]
+const HARD_RELOAD_SCRIPT: &str = r#"(async () => {
+  try {
+    if (window.caches) {
+      for (const key of await caches.keys()) await caches.delete(key);
+    }
+  } catch (_) {}
+  const url = new URL(window.location.href);
+  url.searchParams.set("_onyx_reload", Date.now().toString());
+  window.location.replace(url.toString());
+})();"#;
+
+/// Reload the current page, bypassing the cache so a freshly deployed server
+/// version loads in full
+#[tauri::command]
+fn hard_reload(window: tauri::WebviewWindow) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        window
+            .with_webview(|webview| unsafe {
+                let web_view = webview.inner() as *mut AnyObject;
+                let _: *mut AnyObject = msg_send![web_view, reloadFromOrigin];
+            })
+            .map_err(|e| format!("Failed to reload page: {}", e))
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        window
+            .with_webview(|webview| {
+                use webkit2gtk::WebViewExt;
+
+                webview.inner().reload_bypass_cache();
+            })
+            .map_err(|e| format!("Failed to reload page: {}", e))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        window
+            .eval(HARD_RELOAD_SCRIPT)
+            .map_err(|e| format!("Failed to reload page: {}", e))
+    }
+}
+
+/// Whether each window can go back and forward, as reported by the injected
+/// navigation script. Windows that haven't reported count as neither.
+struct NavigationState {
+    windows: Mutex<HashMap<String, (bool, bool)>>,
+}
+
+fn navigation_state(window: &WebviewWindow) -> (bool, bool) {
+    window
+        .state::<NavigationState>()
+        .windows
+        .lock()
+        .unwrap()
+        .get(window.label())
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Record the calling window's back/forward availability
+#[tauri::command]
+fn set_navigation_state(
+    window: tauri::WebviewWindow,
+    state: tauri::State<NavigationState>,
+    can_go_back: bool,
+    can_go_forward: bool,
+) {
+    state
+        .windows
+        .lock()
+        .unwrap()
+        .insert(window.label().to_string(), (can_go_back, can_go_forward));
+}
+
+/// Whether the window has a page to go back to
+#[tauri::command]
+fn can_go_back(window: tauri::WebviewWindow) -> bool {
+    navigation_state(&window).0
+}
+
+/// Whether the window has a page to go forward to
+#[tauri::command]
+fn can_go_forward(window: tauri::WebviewWindow) -> bool {
+    navigation_state(&window).1
+}
+
+/// Go back in history. Returns whether there was a page to go back to.
+#[tauri::command]
+fn go_back(window: tauri::WebviewWindow) -> bool {
+    let _ = window.eval("window.history.back()");
+    navigation_state(&window).0
+}
+
+/// Go forward in history. Returns whether there was a page to go forward to.
+#[tauri::command]
+fn go_forward(window: tauri::WebviewWindow) -> bool {
+    let _ = window.eval("window.history.forward()");
+    navigation_state(&window).1
+}
+
+/// Navigate the window to the server's home page
+#[tauri::command]
+fn go_home(window: tauri::WebviewWindow) -> Result<(), String> {
+    let url = parse_server_url(&window_server_url(window.app_handle(), window.label()))?;
+    window.navigate(url).map_err(|e| e.to_string())
+}
+
+/// Open the find bar in `window`, installing the find script first if needed
+fn open_find_bar(window: &WebviewWindow) {
+    let _ = window.eval(&format!("{}\nwindow.__onyxFind.open()", FIND_SCRIPT));
+}
+
+/// Highlight matches for `query` and scroll to the next (or previous) one,
+/// wrapping around at either end
+#[tauri::command]
+fn find_in_page(window: tauri::WebviewWindow, query: String, forward: bool) -> Result<(), String> {
+    let query = serde_json::to_string(&query).map_err(|e| e.to_string())?;
+    window
+        .eval(&format!(
+            "{}\nwindow.__onyxFind.find({}, {})",
+            FIND_SCRIPT, query, forward
+        ))
+        .map_err(|e| e.to_string())
+}
+
+/// Clear find highlights and hide the find bar
+#[tauri::command]
+fn stop_find(window: tauri::WebviewWindow) -> Result<(), String> {
+    window
+        .eval("window.__onyxFind && window.__onyxFind.stop()")
+        .map_err(|e| e.to_string())
+}
+
+/// Open the current page in the system browser
+#[tauri::command]
+fn open_in_browser(app: AppHandle, window: tauri::WebviewWindow) {
+    open_page_in_browser(&app, &window);
+}
+
+/// Copy the current page's URL to the clipboard
+#[tauri::command]
+fn copy_current_url(window: tauri::WebviewWindow, app: AppHandle) -> Result<(), String> {
+    let url = window
+        .url()
+        .map_err(|e| format!("Failed to read the current URL: {}", e))?;
+    app.clipboard()
+        .write_text(url.to_string())
+        .map_err(|e| format!("Failed to copy URL to clipboard: {}", e))
+}
+
+/// Chat to open when the user returns to the app from a notification.
+/// Desktop notifications don't report clicks, so the next time a window gains
+/// focus we treat it as the click.
+struct NotificationState {
+    pending_chat_path: Mutex<Option<String>>,
+}
+
+/// Show an OS notification for activity in a background window. Skipped when
+/// the calling window is focused; `chat_path` (e.g. `/chat?chatId=...`) is
+/// opened in the main window when the user comes back to the app.
+#[tauri::command]
+fn show_notification(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    title: String,
+    body: String,
+    chat_path: Option<String>,
+) -> Result<(), String> {
+    if window.is_focused().unwrap_or(false) {
+        return Ok(());
+    }
+
+    if let Some(path) = &chat_path {
+        if !path.starts_with('/') {
+            return Err(format!("Chat path must start with '/': {}", path));
+        }
+    }
+
+    app.notification()
+        .builder()
+        .title(title)
+        .body(body)
+        .show()
+        .map_err(|e| format!("Failed to show notification: {}", e))?;
+
+    *app.state::<NotificationState>()
+        .pending_chat_path
+        .lock()
+        .unwrap() = chat_path;
+    Ok(())
+}
+
+/// Open the chat from the last notification, if any, once the app is focused
+fn handle_notification_focus(app: &AppHandle) {
+    let pending = app
+        .state::<NotificationState>()
+        .pending_chat_path
+        .lock()
+        .unwrap()
+        .take();
+
+    if let Some(path) = pending {
+        open_path(app, &path);
+    }
+}
+
+/// Show the unread count on the Dock (macOS) or launcher (Linux/Unity) icon.
+/// Windows has no numeric badge, so a taskbar overlay marks unread activity.
+/// `None` or `0` clears the badge.
+#[tauri::command]
+fn set_badge_count(app: AppHandle, count: Option<u32>) -> Result<(), String> {
+    let count = count.filter(|&count| count > 0);
+    let window = app
+        .get_webview_window("main")
+        .or_else(|| focused_window(&app))
+        .ok_or("No window available to badge")?;
+
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    window
+        .set_badge_count(count.map(i64::from))
+        .map_err(|e| format!("Failed to set badge count: {}", e))?;
+
+    #[cfg(target_os = "windows")]
+    window
+        .set_overlay_icon(count.and_then(|_| app.default_window_icon().cloned()))
+        .map_err(|e| format!("Failed to set taskbar overlay: {}", e))?;
+
+    Ok(())
+}
+
+/// Unread count last reported by the web app, shown on the tray icon
+struct UnreadState {
+    count: Mutex<u32>,
+}
+
+/// Report unread messages: badges the Dock or taskbar icon, marks the tray
+/// icon with a dot, and adds the count to the tray tooltip. `0` clears all
+/// three.
+#[tauri::command]
+fn set_unread_count(app: AppHandle, count: u32) -> Result<(), String> {
+    *app.state::<UnreadState>().count.lock().unwrap() = count;
+    refresh_tray_status(&app);
+    set_badge_count(app, Some(count))
+}
+
+/// Labels of the windows currently pinned above other apps. Kept natively,
+/// so it survives page reloads but not restarts.
+struct AlwaysOnTopState {
+    labels: Mutex<HashSet<String>>,
+}
+
+fn is_always_on_top(app: &AppHandle, label: &str) -> bool {
+    app.state::<AlwaysOnTopState>()
+        .labels
+        .lock()
+        .unwrap()
+        .contains(label)
+}
+
+/// Sync the Window menu checkmark with the given window's pin state
+fn refresh_always_on_top_menu(app: &AppHandle, label: &str) {
+    let item = app
+        .menu()
+        .and_then(|menu| menu.get(WINDOW_SUBMENU_ID))
+        .and_then(|item| item.as_submenu().cloned())
+        .and_then(|submenu| submenu.get(ALWAYS_ON_TOP_MENU_ID))
+        .and_then(|item| item.as_check_menuitem().cloned());
+
+    if let Some(item) = item {
+        let _ = item.set_checked(is_always_on_top(app, label));
+    }
+}
+
+/// Pin or unpin a window above other apps
+fn apply_always_on_top(app: &AppHandle, window: &WebviewWindow, on_top: bool) -> tauri::Result<()> {
+    window.set_always_on_top(on_top)?;
+
+    {
+        let state = app.state::<AlwaysOnTopState>();
+        let mut labels = state.labels.lock().unwrap();
+        if on_top {
+            labels.insert(window.label().to_string());
+        } else {
+            labels.remove(window.label());
+        }
+    }
+
+    refresh_always_on_top_menu(app, window.label());
+    Ok(())
+}
+
+/// Pin or unpin the calling window above other apps, and remember the choice
+/// for new windows
+#[tauri::command]
+fn set_always_on_top(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    enabled: bool,
+) -> Result<bool, String> {
+    ensure_unmanaged("always_on_top", enabled.into())?;
+    apply_always_on_top(&app, &window, enabled)
+        .map_err(|e| format!("Failed to set always on top: {}", e))?;
+
+    let state = app.state::<ConfigState>();
+    let mut config = state.config.write().unwrap();
+    if config.always_on_top != enabled {
+        config.always_on_top = enabled;
+        save_config(&config)?;
+        emit_config_changed(&app, &config);
+    }
+    Ok(enabled)
+}
+
+/// Flip the window's always-on-top state, returning the new state
+#[tauri::command]
+fn toggle_always_on_top(app: AppHandle, window: tauri::WebviewWindow) -> Result<bool, String> {
+    let on_top = !is_always_on_top(&app, window.label());
+    set_always_on_top(app, window, on_top)
+}
+
+/// A window's geometry before it went compact, in logical pixels
+#[derive(Debug, Clone, Copy)]
+struct SavedGeometry {
+    width: f64,
+    height: f64,
+    x: f64,
+    y: f64,
+    maximized: bool,
+    on_top: bool,
+}
+
+// Windows in compact mode, keyed by label, with what to restore them to
+struct CompactState {
+    windows: Mutex<HashMap<String, SavedGeometry>>,
+}
+
+/// Top-left corner for a `window`-sized window docked in the bottom-right of
+/// a work area at `origin` with size `area`, `COMPACT_WINDOW_MARGIN` from the
+/// edges. Never above or left of the area, even if the window doesn't fit.
+fn compact_position(origin: (f64, f64), area: (f64, f64), window: (f64, f64)) -> (f64, f64) {
+    let x = origin.0 + (area.0 - window.0 - COMPACT_WINDOW_MARGIN).max(0.0);
+    let y = origin.1 + (area.1 - window.1 - COMPACT_WINDOW_MARGIN).max(0.0);
+    (x, y)
+}
+
+/// Shrink `window` to a small always-on-top window in the bottom-right corner
+/// of its screen, remembering its geometry for `restore_from_compact`
+fn enter_compact(app: &AppHandle, window: &WebviewWindow) -> tauri::Result<()> {
+    let scale = window.scale_factor()?;
+    let size = window.inner_size()?.to_logical::<f64>(scale);
+    let position = window.outer_position()?.to_logical::<f64>(scale);
+    let saved = SavedGeometry {
+        width: size.width,
+        height: size.height,
+        x: position.x,
+        y: position.y,
+        maximized: window.is_maximized()?,
+        on_top: is_always_on_top(app, window.label()),
+    };
+
+    if window.is_fullscreen()? {
+        window.set_fullscreen(false)?;
+    }
+    if saved.maximized {
+        window.unmaximize()?;
+    }
+    // The usual minimum size is larger than the compact one
+    window.set_min_size(None::<LogicalSize<f64>>)?;
+    window.set_size(LogicalSize::new(
+        COMPACT_WINDOW_WIDTH,
+        COMPACT_WINDOW_HEIGHT,
+    ))?;
+    if let Some(monitor) = window.current_monitor()? {
+        let scale = monitor.scale_factor();
+        let work_area = monitor.work_area();
+        let origin = work_area.position.to_logical::<f64>(scale);
+        let area = work_area.size.to_logical::<f64>(scale);
+        let (x, y) = compact_position(
+            (origin.x, origin.y),
+            (area.width, area.height),
+            (COMPACT_WINDOW_WIDTH, COMPACT_WINDOW_HEIGHT),
+        );
+        window.set_position(LogicalPosition::new(x, y))?;
+    }
+    apply_always_on_top(app, window, true)?;
+
+    app.state::<CompactState>()
+        .windows
+        .lock()
+        .unwrap()
+        .insert(window.label().to_string(), saved);
+    Ok(())
+}
+
+/// Put a compact window back the way it was before `enter_compact`
+fn restore_from_compact(
+    app: &AppHandle,
+    window: &WebviewWindow,
+    saved: SavedGeometry,
+) -> tauri::Result<()> {
+    let sizes = app.state::<ConfigState>().window_sizes();
+    window.set_min_size(Some(LogicalSize::new(sizes.min_width, sizes.min_height)))?;
+    window.set_size(LogicalSize::new(saved.width, saved.height))?;
+    window.set_position(LogicalPosition::new(saved.x, saved.y))?;
+    if saved.maximized {
+        window.maximize()?;
+    }
+    apply_always_on_top(app, window, saved.on_top)
+}
+
+/// Switch the window between compact mode (small, pinned, bottom-right) and
+/// its previous size and position. Returns whether it's now compact.
+#[tauri::command]
+fn toggle_compact(app: AppHandle, window: tauri::WebviewWindow) -> Result<bool, String> {
+    let saved = app
+        .state::<CompactState>()
+        .windows
+        .lock()
+        .unwrap()
+        .remove(window.label());
+
+    match saved {
+        Some(saved) => {
+            restore_from_compact(&app, &window, saved)
+                .map_err(|e| format!("Failed to leave compact mode: {}", e))?;
+            Ok(false)
+        }
+        None => {
+            enter_compact(&app, &window)
+                .map_err(|e| format!("Failed to enter compact mode: {}", e))?;
+            Ok(true)
+        }
+    }
+}
+
+/// Ask a yes/no question in a native dialog, with `confirm_label` and Cancel
+/// buttons. Resolves to whether the user confirmed.
+async fn confirm_dialog(
+    app: &AppHandle,
+    title: &str,
+    message: &str,
+    kind: MessageDialogKind,
+    confirm_label: &str,
+) -> Result<bool, String> {
+    let dialog = app
+        .dialog()
+        .message(message)
+        .title(title)
+        .kind(kind)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            confirm_label.to_string(),
+            "Cancel".to_string(),
+        ));
+
+    tauri::async_runtime::spawn_blocking(move || dialog.blocking_show())
+        .await
+        .map_err(|e| format!("Failed to show confirmation: {}", e))
+}
+
+/// Show an informational native dialog without waiting for it to close
+fn message_dialog(app: &AppHandle, title: &str, message: &str, kind: MessageDialogKind) {
+    app.dialog()
+        .message(message)
+        .title(title)
+        .kind(kind)
+        .show(|_| {});
+}
+
+/// Clear cookies, cache, and storage for all windows after the user confirms,
+/// then send every web app window back to the login page. Returns without
+/// clearing anything if the user cancels.
+#[tauri::command]
+async fn clear_session_data(app: AppHandle) -> Result<(), String> {
+    let confirmed = confirm_dialog(
+        &app,
+        "Log Out and Clear Data?",
+        "This signs you out and clears cookies, cache, and site data for all Onyx windows.",
+        MessageDialogKind::Warning,
+        "Clear Data",
+    )
+    .await?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    data_window(&app)?
+        .clear_all_browsing_data()
+        .map_err(|e| format!("Failed to clear browsing data: {}", e))?;
+
+    for window in web_app_windows(&app) {
+        let server_url = window_server_url(&app, window.label());
+        let login_url = parse_server_url(&format!("{}{}", server_url, LOGIN_PATH))?;
+        let _ = window.navigate(login_url);
+    }
+
+    Ok(())
+}
+
+/// A window to clear data through. Every window shares one data store, so
+/// any of them will do.
+fn data_window(app: &AppHandle) -> Result<tauri::WebviewWindow, String> {
+    app.webview_windows()
+        .into_values()
+        .find(|window| window.label() != SETTINGS_WINDOW_LABEL)
+        .ok_or_else(|| "No window available to clear data from".to_string())
+}
+
+/// Windows showing the web app (or that should be: `main` may be on the
+/// offline page)
+fn web_app_windows(app: &AppHandle) -> Vec<tauri::WebviewWindow> {
+    app.webview_windows()
+        .into_values()
+        .filter(|window| {
+            let on_web_app = window
+                .url()
+                .map(|url| matches!(url.scheme(), "http" | "https"))
+                .unwrap_or(false);
+            on_web_app || window.label() == "main"
+        })
+        .collect()
+}
+
+/// Send a window back to its server's root
+fn navigate_to_server_root(app: &AppHandle, window: &tauri::WebviewWindow) -> Result<(), String> {
+    let root = parse_server_url(&window_server_url(app, window.label()))?;
+    window
+        .navigate(root)
+        .map_err(|e| format!("Failed to navigate {}: {}", window.label(), e))
+}
+
+/// Clear cookies, cache, and storage for all windows without asking, then
+/// send every web app window back to its server's root. For frontends that
+/// confirm on their own, e.g. before signing in to another tenant.
+#[tauri::command]
+fn clear_session(app: AppHandle) -> Result<(), String> {
+    data_window(&app)?
+        .clear_all_browsing_data()
+        .map_err(|e| format!("Failed to clear browsing data: {}", e))?;
+
+    for window in web_app_windows(&app) {
+        navigate_to_server_root(&app, &window)?;
+    }
+
+    info!("Cleared session data for all servers");
+    Ok(())
+}
+
+/// Clear the session for one server only: delete its cookies and the local
+/// and session storage of windows showing it, then send those windows back to
+/// its root. Other servers stay signed in. The HTTP cache is shared and can't
+/// be cleared per origin, so it's left alone.
+#[tauri::command]
+fn clear_session_for_url(app: AppHandle, url: String) -> Result<(), String> {
+    let origin = parse_server_url(&validate_server_url(&url)?)?;
+
+    let window = data_window(&app)?;
+    let cookies = window
+        .cookies_for_url(origin.clone())
+        .map_err(|e| format!("Failed to read cookies for {}: {}", origin, e))?;
+    for cookie in cookies {
+        window
+            .delete_cookie(cookie)
+            .map_err(|e| format!("Failed to delete cookie for {}: {}", origin, e))?;
+    }
+
+    for window in web_app_windows(&app) {
+        let on_origin = window
+            .url()
+            .map(|current| current.origin() == origin.origin())
+            .unwrap_or(false);
+        if on_origin {
+            let _ = window.eval("localStorage.clear(); sessionStorage.clear();");
+            navigate_to_server_root(&app, &window)?;
+        }
+    }
+
+    info!("Cleared session data for {}", origin);
+    Ok(())
+}
+
+/// Open a new window
+#[tauri::command]
+async fn new_window(app: AppHandle, state: tauri::State<'_, ConfigState>) -> Result<(), String> {
+    let server_url = state.server_url();
+    open_server_window(&app, &state, &server_url)?;
+    Ok(())
+}
+
+/// Open a new window on `server_url`, which may differ from the active
+/// server. The window stays on that server when the active one changes.
+#[tauri::command]
+async fn new_window_for_server(
+    app: AppHandle,
+    state: tauri::State<'_, ConfigState>,
+    server_url: String,
+) -> Result<(), String> {
+    let server_url = validate_server_url(&server_url)?;
+    let window = open_server_window(&app, &state, &server_url)?;
+
+    if server_url != state.server_url() {
+        app.state::<WindowServerState>()
+            .servers
+            .write()
+            .unwrap()
+            .insert(window.label().to_string(), server_url);
+    }
+
+    Ok(())
+}
+
+/// Build an app window showing `server_url`
+fn open_server_window(
+    app: &AppHandle,
+    state: &ConfigState,
+    server_url: &str,
+) -> Result<WebviewWindow, String> {
+    let window_label = format!("onyx-{}", uuid::Uuid::new_v4());
+    let (sizes, position) = new_window_placement(app, state, server_url);
+
+    let builder = WebviewWindowBuilder::new(
+        app,
+        &window_label,
+        WebviewUrl::External(parse_server_url(server_url)?),
+    )
+    .title(state.window_title())
+    .inner_size(sizes.width, sizes.height)
+    .min_inner_size(sizes.min_width, sizes.min_height)
+    .transparent(!state.vibrancy_disabled());
+    let builder = match position {
+        Some(position) => builder.position(position.x, position.y),
+        None => builder,
+    };
+
+    #[cfg(target_os = "macos")]
+    let builder = builder
+        .title_bar_style(tauri::TitleBarStyle::Overlay)
+        .hidden_title(true);
+
+    #[cfg(target_os = "linux")]
+    let builder =
+        builder.background_color(state.background_color().unwrap_or(DEFAULT_BACKGROUND_COLOR));
+
+    let builder = apply_drag_drop(builder, state.native_drag_drop());
+    let builder = apply_download_handler(apply_proxy(
+        apply_user_agent(builder, state.user_agent().as_deref()),
+        state.proxy_url(),
+    ));
+
+    let window = builder.build().map_err(|e| e.to_string())?;
+    let zoom_level = state.config.read().unwrap().zoom_level_for(server_url);
+    let _ = window.set_zoom(zoom_level);
+
+    // Apply vibrancy/background for the theme; the titlebar is injected on page load
+    apply_window_theme(&window);
+    if state.always_on_top() {
+        let _ = apply_always_on_top(app, &window, true);
+    }
+
+    Ok(window)
+}
+
+/// An open window, as listed for window switchers
+#[derive(Debug, Clone, Serialize)]
+struct WindowInfo {
+    label: String,
+    url: Option<String>,
+    title: Option<String>,
+    focused: bool,
+    visible: bool,
+}
+
+/// List every open window, ordered by label
+#[tauri::command]
+fn list_windows(app: AppHandle) -> Vec<WindowInfo> {
+    let mut windows: Vec<WindowInfo> = app
+        .webview_windows()
+        .into_values()
+        .map(|window| WindowInfo {
+            label: window.label().to_string(),
+            url: window.url().ok().map(|url| url.to_string()),
+            title: window.title().ok(),
+            focused: window.is_focused().unwrap_or(false),
+            visible: window.is_visible().unwrap_or(false),
+        })
+        .collect();
+    windows.sort_by(|a, b| a.label.cmp(&b.label));
+    windows
+}
+
+/// Show and focus the window with `label`
+#[tauri::command]
+fn focus_window(app: AppHandle, label: String) -> Result<(), String> {
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| format!("No window with label '{}'", label))?;
+    let _ = window.unminimize();
+    window
+        .show()
+        .map_err(|e| format!("Failed to show window: {}", e))?;
+    window
+        .set_focus()
+        .map_err(|e| format!("Failed to focus window: {}", e))
+}
+
+/// Minimize every Onyx window to clear the screen. Windows that are already
+/// minimized or hidden are left alone.
+#[tauri::command]
+fn minimize_all(app: AppHandle) {
+    for window in app.webview_windows().into_values() {
+        if !is_app_window(window.label())
+            || window.is_minimized().unwrap_or(false)
+            || !window.is_visible().unwrap_or(true)
+        {
+            continue;
+        }
+        if let Err(e) = window.minimize() {
+            warn!("Failed to minimize window {}: {}", window.label(), e);
+        }
+    }
+}
+
+/// Bring back every Onyx window minimized or hidden to the tray, then focus
+/// the main one
+#[tauri::command]
+fn show_all(app: AppHandle) {
+    for window in app.webview_windows().into_values() {
+        if !is_app_window(window.label()) {
+            continue;
+        }
+        if let Err(e) = window.unminimize().and_then(|_| window.show()) {
+            warn!("Failed to show window {}: {}", window.label(), e);
+        }
+    }
+    if let Some(window) = summon_target_window(&app) {
+        let _ = window.set_focus();
+    }
+}
+
+/// Close the window with `label`, as if the user clicked its close button
+#[tauri::command]
+fn close_window(app: AppHandle, label: String) -> Result<(), String> {
+    let window = app
+        .get_webview_window(&label)
+        .ok_or_else(|| format!("No window with label '{}'", label))?;
+    window
+        .close()
+        .map_err(|e| format!("Failed to close window: {}", e))
+}
+
+/// Get the zoom level saved for the calling window's server
+#[tauri::command]
+fn get_zoom_level(window: tauri::WebviewWindow, state: tauri::State<ConfigState>) -> f64 {
+    let server_url = window_server_url(window.app_handle(), window.label());
+    state.config.read().unwrap().zoom_level_for(&server_url)
+}
+
+/// Set, persist, and apply the zoom level for the calling window's server.
+/// Returns the level actually applied after clamping.
+#[tauri::command]
+fn set_zoom_level(window: tauri::WebviewWindow, level: f64) -> Result<f64, String> {
+    let server_url = window_server_url(window.app_handle(), window.label());
+    apply_zoom_level(window.app_handle(), &server_url, level)
+}
+
+/// Reset the calling window's server to 100% zoom, in every window showing it
+#[tauri::command]
+fn reset_zoom(window: tauri::WebviewWindow) -> Result<f64, String> {
+    set_zoom_level(window, DEFAULT_ZOOM_LEVEL)
+}
+
+/// Open the settings window
+#[tauri::command]
+async fn open_settings_window(app: AppHandle) -> Result<(), String> {
+    show_settings_window(&app).map_err(|e| e.to_string())
+}
+
+/// Close the settings window, optionally loading the (new) server URL in the main window
+#[tauri::command]
+fn close_settings_window(app: AppHandle, apply: bool) -> Result<(), String> {
+    if apply {
+        let server_url = parse_server_url(&app.state::<ConfigState>().server_url())?;
+        if let Some(window) = app.get_webview_window("main") {
+            window.navigate(server_url).map_err(|e| e.to_string())?;
+        }
+    }
+
+    if let Some(window) = app.get_webview_window(SETTINGS_WINDOW_LABEL) {
+        window.close().map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Register or unregister the OS login item to match `enabled`
+fn apply_launch_at_login(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let autolaunch = app.autolaunch();
+    let registered = autolaunch.is_enabled().unwrap_or(!enabled);
+    if registered == enabled {
+        return Ok(());
+    }
+
+    if enabled {
+        autolaunch.enable()
+    } else {
+        autolaunch.disable()
+    }
+    .map_err(|e| format!("Failed to update launch at login: {}", e))
+}
+
+/// Whether Onyx starts automatically at login
+#[tauri::command]
+fn get_launch_at_login(state: tauri::State<ConfigState>) -> bool {
+    state.config.read().unwrap().launch_at_login
+}
+
+/// Enable or disable starting Onyx automatically at login
+#[tauri::command]
+fn set_launch_at_login(
+    app: AppHandle,
+    state: tauri::State<ConfigState>,
+    enabled: bool,
+) -> Result<bool, String> {
+    ensure_unmanaged("launch_at_login", enabled.into())?;
+    apply_launch_at_login(&app, enabled)?;
+
+    let mut config = state.config.write().unwrap();
+    config.launch_at_login = enabled;
+    save_config(&config)?;
+    emit_config_changed(&app, &config);
+
+    Ok(enabled)
+}
+
+/// Save an HTTP or SOCKS5 proxy for the webview and connection checks.
+/// Webviews only read it when they're created, so with `restart` the main
+/// window is reopened to apply it right away. Returns the saved proxy URL.
+#[tauri::command]
+async fn set_proxy(app: AppHandle, url: String, restart: Option<bool>) -> Result<String, String> {
+    parse_proxy_url(&url)?;
+    let url = url.trim().to_string();
+    save_proxy(&app, Some(url.clone()), restart.unwrap_or(false)).await?;
+    Ok(url)
+}
+
+/// Go back to the system proxy; see `set_proxy`
+#[tauri::command]
+async fn clear_proxy(app: AppHandle, restart: Option<bool>) -> Result<(), String> {
+    save_proxy(&app, None, restart.unwrap_or(false)).await
+}
+
+/// Persist `proxy_url`, reopening the main window if it changed and `restart`
+/// is set
+async fn save_proxy(
+    app: &AppHandle,
+    proxy_url: Option<String>,
+    restart: bool,
+) -> Result<(), String> {
+    ensure_unmanaged(
+        "proxy_url",
+        proxy_url
+            .clone()
+            .map_or(serde_json::Value::Null, serde_json::Value::from),
+    )?;
+
+    let changed = {
+        let state = app.state::<ConfigState>();
+        let mut config = state.config.write().unwrap();
+        let changed = config.proxy_url != proxy_url;
+        config.proxy_url = proxy_url;
+        save_config(&config)?;
+        *state.config_initialized.write().unwrap() = true;
+        emit_config_changed(app, &config);
+        changed
+    };
+
+    if changed && restart {
+        recreate_main_window(app).await?;
+    }
+    Ok(())
+}
+
+/// Get whether the tray icon is shown
+#[tauri::command]
+fn get_show_tray_icon(state: tauri::State<ConfigState>) -> bool {
+    state.config.read().unwrap().show_tray_icon
+}
+
+/// Show or hide the tray icon and remember the choice
+#[tauri::command]
+fn set_show_tray_icon(
+    app: AppHandle,
+    state: tauri::State<ConfigState>,
+    visible: bool,
+) -> Result<bool, String> {
+    ensure_unmanaged("show_tray_icon", visible.into())?;
+    {
+        let mut config = state.config.write().unwrap();
+        config.show_tray_icon = visible;
+        save_config(&config)?;
+        emit_config_changed(&app, &config);
+    }
+
+    apply_tray_icon_visibility(&app, visible);
+    Ok(visible)
+}
+
+/// Reset config to defaults. Returns whether a restart is needed to fully
+/// apply the reset (see `relaunch_app`).
+#[tauri::command]
+fn reset_config(app: AppHandle, state: tauri::State<ConfigState>) -> Result<bool, String> {
+    let previous_url = state.server_url();
+
+    let needs_restart = {
+        let mut config = state.config.write().unwrap();
+        // Managed settings survive a reset
+        let defaults = apply_managed_config(&AppConfig::default(), managed_config());
+        let needs_restart = config.needs_restart_from(&defaults);
+        *config = defaults;
+        save_config(&config)?;
+        *state.config_initialized.write().unwrap() = true;
+        emit_config_changed(&app, &config);
+
+        if let Err(e) = apply_launch_at_login(&app, config.launch_at_login) {
+            error!("{}", e);
+        }
+        apply_tray_icon_visibility(&app, config.show_tray_icon);
+
+        needs_restart
+    };
+
+    move_windows_to_server(&app, &previous_url, &default_server_url());
+
+    Ok(needs_restart)
+}
+
+/// Check a config from outside the app (an import or a patch): URLs must be
+/// valid and window sizes positive. Server URLs and the title are normalized.
+fn validate_config(config: &mut AppConfig) -> Result<(), String> {
+    config.server_url = validate_server_url(&config.server_url)?;
+    for profile in &mut config.servers {
+        profile.url = validate_server_url(&profile.url)
+            .map_err(|e| format!("Server '{}': {}", profile.name, e))?;
+    }
+    if let Some(proxy_url) = &config.proxy_url {
+        parse_proxy_url(proxy_url)?;
+    }
+    if config
+        .basic_auth_user
+        .as_deref()
+        .is_some_and(|user| user.contains(':'))
+    {
+        return Err("basic_auth_user can't contain ':'".to_string());
+    }
+
+    for (name, value) in [
+        ("default_width", config.default_width),
+        ("default_height", config.default_height),
+        ("min_width", config.min_width),
+        ("min_height", config.min_height),
+    ] {
+        if !(value.is_finite() && value > 0.0) {
+            return Err(format!("{} must be a positive number", name));
+        }
+    }
+
+    config.window_title = normalize_window_title(&config.window_title);
+    config.sync_server_profiles();
+    Ok(())
+}
+
+/// Apply a JSON object of top-level config fields over `config`. Nested
+/// values such as `shortcuts` are replaced whole. Unknown keys and values of
+/// the wrong type are errors.
+fn merge_config_patch(config: &AppConfig, patch: &serde_json::Value) -> Result<AppConfig, String> {
+    let patch = patch
+        .as_object()
+        .ok_or("Config patch must be a JSON object")?;
+    let mut merged = config_value_with_secrets(config)?;
+    let fields = merged
+        .as_object_mut()
+        .ok_or("Config did not serialize to an object")?;
+
+    for (key, value) in patch {
+        match fields.get_mut(key) {
+            Some(field) => *field = value.clone(),
+            None => return Err(format!("Unknown config key '{}'", key)),
+        }
+    }
+
+    serde_json::from_value(merged).map_err(|e| format!("Invalid config patch: {}", e))
+}
+
+/// Get the full config as saved in config.json
+#[tauri::command]
+fn get_config(state: tauri::State<ConfigState>) -> AppConfig {
+    state.config.read().unwrap().clone()
+}
+
+/// Whether `window` is the Settings window showing the bundled Settings page,
+/// rather than a page loaded from a server
+fn is_settings_page(window: &WebviewWindow) -> bool {
+    let settings = bundled_page_url(SETTINGS_PAGE);
+    window.label() == SETTINGS_WINDOW_LABEL
+        && window.url().is_ok_and(|url| {
+            url.scheme() == settings.scheme()
+                && url.host_str() == settings.host_str()
+                && url.path() == settings.path()
+        })
+}
+
+/// Merge `patch` into the config (see `merge_config_patch`), then validate,
+/// apply, and save the result. Returns the new config; nothing changes if the
+/// patch is rejected. Only the Settings window may call this, and the server
+/// URL has to go through `set_server_url` so it's checked for reachability.
+#[tauri::command]
+fn update_config(
+    app: AppHandle,
+    window: tauri::WebviewWindow,
+    patch: serde_json::Value,
+) -> Result<AppConfig, String> {
+    if !is_settings_page(&window) {
+        return Err("The config can only be changed from Settings".to_string());
+    }
+
+    let current = app.state::<ConfigState>().config.read().unwrap().clone();
+    let mut config = merge_config_patch(&current, &patch)?;
+    if let Some(patch) = patch.as_object() {
+        check_managed_patch(patch, managed_config())?;
+    }
+    validate_config(&mut config)?;
+    if config.server_url != current.server_url {
+        return Err("Use set_server_url to change the server URL".to_string());
+    }
+
+    save_config(&config)?;
+    replace_config(&app, config.clone());
+    Ok(config)
+}
+
+/// Ask where to save the current config as JSON, e.g. to move it to another
+/// machine, and write it there. The user picks the file in a native dialog,
+/// so the page can't write anywhere else. Returns where it was saved, or
+/// `None` if the user cancels.
+#[tauri::command]
+async fn export_config(app: AppHandle) -> Result<Option<String>, String> {
+    let Some(path) = choose_config_file(&app, true).await? else {
+        return Ok(None);
+    };
+
+    let mut value = serde_json::to_value(&*app.state::<ConfigState>().config.read().unwrap())
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    // Export the user's own settings, not this machine's managed ones
+    restore_user_layer(&mut value, &get_config_path())?;
+    let json = serde_json::to_string_pretty(&value)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    write_file_atomically(&path, &json)?;
+    info!("Exported config to {}", path.display());
+    Ok(Some(path.display().to_string()))
+}
+
+/// Ask for a config exported with `export_config`, then apply and save it.
+/// Nothing changes if the file is unreadable or invalid. Returns the new
+/// config, or `None` if the user cancels.
+#[tauri::command]
+async fn import_config(app: AppHandle) -> Result<Option<AppConfig>, String> {
+    let Some(path) = choose_config_file(&app, false).await? else {
+        return Ok(None);
+    };
+
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let (config, _) = parse_config(&contents).map_err(|e| format!("Invalid config file: {}", e))?;
+    // Imported values for managed settings are quietly replaced
+    let mut config = apply_managed_config(&config, managed_config());
+    validate_config(&mut config)?;
+
+    save_config(&config)?;
+    replace_config(&app, config.clone());
+    info!("Imported config from {}", path.display());
+    Ok(Some(config))
+}
+
+/// Ask where to export the config to (`save`) or which file to import.
+/// Returns `None` if the user cancels.
+async fn choose_config_file(app: &AppHandle, save: bool) -> Result<Option<PathBuf>, String> {
+    let dialog = app
+        .dialog()
+        .file()
+        .add_filter("JSON", &["json"])
+        .set_file_name("onyx-config.json");
+
+    let path = tauri::async_runtime::spawn_blocking(move || {
+        if save {
+            dialog.blocking_save_file()
+        } else {
+            dialog.blocking_pick_file()
+        }
+    })
+    .await
+    .map_err(|e| format!("Failed to show file dialog: {}", e))?;
+
+    path.map(|path| {
+        path.into_path()
+            .map_err(|e| format!("Invalid file path: {}", e))
+    })
+    .transpose()
+}
+
+/// Restart Onyx after the user confirms, so settings the running webviews
+/// can't pick up (User-Agent, certificates) take effect. Config and the open
+/// windows are saved first. Returns only if the user cancels.
+#[tauri::command]
+async fn relaunch_app(app: AppHandle) -> Result<(), String> {
+    let confirmed = confirm_dialog(
+        &app,
+        "Restart Onyx?",
+        "Onyx will restart to apply your changes. Your open windows will be restored.",
+        MessageDialogKind::Info,
+        "Restart",
+    )
+    .await?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    restart_app(&app)
+}
+
+/// Save config and the open windows, close every window, and restart. Only
+/// returns if the config couldn't be saved.
+fn restart_app(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<ConfigState>();
+    if *state.config_initialized.read().unwrap() {
+        save_config(&state.config.read().unwrap())?;
+    }
+    if let Err(e) = save_window_session(&capture_window_session(app)) {
+        error!("Failed to save window session: {}", e);
+    }
+    // The windows closed below belong in the session
+    *app.state::<QuitState>().flushed.lock().unwrap() = true;
+
+    // Closing skips close-to-tray and lets each webview shut down normally
+    for window in app.webview_windows().values() {
+        if let Err(e) = window.destroy() {
+            warn!("Failed to close {}: {}", window.label(), e);
+        }
+    }
+
+    info!("Restarting Onyx");
+    app.restart()
+}
+
+/// Apply the config's TLS settings to the webview. Windows share one web
+/// context, so applying this to the main window covers every window.
+fn apply_webview_tls_settings(window: &WebviewWindow, config: &AppConfig) {
+    if !config.danger_accept_invalid_certs {
+        return;
+    }
+
+    warn!("danger_accept_invalid_certs is enabled; TLS certificates are not being verified");
+
+    #[cfg(target_os = "linux")]
+    {
+        let _ = window.with_webview(|webview| {
+            use webkit2gtk::{TLSErrorsPolicy, WebViewExt, WebsiteDataManagerExt};
+
+            if let Some(manager) = webview.inner().website_data_manager() {
+                manager.set_tls_errors_policy(TLSErrorsPolicy::Ignore);
+            }
+        });
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = window;
+        warn!(
+            "The webview on this platform still verifies certificates; trust the server's CA in the system keychain/certificate store instead"
+        );
+    }
+}
+
+/// Answer the saved server's basic auth challenges with the configured
+/// credentials. Only WebKitGTK lets us intercept them; other webviews show
+/// their own prompt. The credentials are kept for the session, which every
+/// window shares, and never put in a page URL.
+#[cfg(target_os = "linux")]
+fn apply_webview_basic_auth(window: &WebviewWindow) {
+    let app = window.app_handle().clone();
+    let _ = window.with_webview(move |webview| {
+        use webkit2gtk::{
+            AuthenticationRequestExt, AuthenticationScheme, Credential, CredentialPersistence,
+            WebViewExt,
+        };
+
+        webview.inner().connect_authenticate(move |_, request| {
+            // A retry means the credentials were wrong; let WebKit ask instead
+            if request.is_for_proxy()
+                || request.is_retry()
+                || request.scheme() != AuthenticationScheme::HttpBasic
+            {
+                return false;
+            }
+
+            let state = app.state::<ConfigState>();
+            let config = state.config.read().unwrap();
+            let Some(auth) = config.basic_auth_for(&config.server_url) else {
+                return false;
+            };
+            let Ok(server) = Url::parse(&config.server_url) else {
+                return false;
+            };
+            let same_server = request.host().as_deref() == server.host_str()
+                && Some(request.port() as u16) == server.port_or_known_default();
+            if !same_server {
+                return false;
+            }
+
+            let credential =
+                Credential::new(&auth.user, &auth.pass, CredentialPersistence::ForSession);
+            request.authenticate(Some(&credential));
+            true
+        });
+    });
+}
+
+/// Reset the window to its default size and center it on screen
+#[tauri::command]
+fn reset_window_geometry(window: tauri::WebviewWindow) -> Result<(), String> {
+    reset_window_to_default(&window).map_err(|e| e.to_string())
+}
+
+/// Start dragging the window
+#[tauri::command]
+async fn start_drag_window(window: tauri::Window) -> Result<(), String> {
+    window.start_dragging().map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// Shortcuts Setup
+// ============================================================================
+
+/// Primary shortcut modifier: Cmd on macOS, Ctrl elsewhere (matches `CmdOrCtrl`)
+#[cfg(target_os = "macos")]
+const PRIMARY_MODIFIER: Modifiers = Modifiers::SUPER;
+#[cfg(not(target_os = "macos"))]
+const PRIMARY_MODIFIER: Modifiers = Modifiers::CONTROL;
+
+/// Resolve the shortcut for `action`, preferring an accelerator string from
+/// the config's `shortcuts` map and falling back to `default` when it is absent
+/// or fails to parse.
+fn configured_shortcut(
+    overrides: &HashMap<String, String>,
+    action: &str,
+    default: Shortcut,
+) -> Shortcut {
+    match overrides.get(action) {
+        Some(accelerator) => Shortcut::from_str(accelerator).unwrap_or_else(|e| {
+            warn!(
+                "Invalid shortcut '{}' for '{}': {}; using default",
+                accelerator, action, e
+            );
+            default
+        }),
+        None => default,
+    }
+}
+
+fn setup_shortcuts(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let overrides = app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .shortcuts
+        .clone();
+
+    let new_chat = configured_shortcut(
+        &overrides,
+        "new_chat",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::KeyN),
+    );
+    #[cfg(not(target_os = "macos"))]
+    let reload = configured_shortcut(
+        &overrides,
+        "reload",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::KeyR),
+    );
+    let back = configured_shortcut(
+        &overrides,
+        "back",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::BracketLeft),
+    );
+    let forward = configured_shortcut(
+        &overrides,
+        "forward",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::BracketRight),
+    );
+    let new_window_shortcut = configured_shortcut(
+        &overrides,
+        "new_window",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyN),
+    );
+    let show_app = configured_shortcut(
+        &overrides,
+        "show_app",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::Space),
+    );
+    let open_settings_shortcut = configured_shortcut(
+        &overrides,
+        "open_settings",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::Comma),
+    );
+    let zoom_in = configured_shortcut(
+        &overrides,
+        "zoom_in",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::Equal),
+    );
+    let zoom_out = configured_shortcut(
+        &overrides,
+        "zoom_out",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::Minus),
+    );
+    let reset_zoom = configured_shortcut(
+        &overrides,
+        "reset_zoom",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::Digit0),
+    );
+    let find = configured_shortcut(
+        &overrides,
+        "find",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::KeyF),
+    );
+    let open_in_browser_shortcut = configured_shortcut(
+        &overrides,
+        "open_in_browser",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyO),
+    );
+    let quick_chat = configured_shortcut(
+        &overrides,
+        "quick_chat",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyJ),
+    );
+    let always_on_top = configured_shortcut(
+        &overrides,
+        "always_on_top",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyT),
+    );
+    let go_home_shortcut = configured_shortcut(
+        &overrides,
+        "go_home",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyH),
+    );
+    #[cfg(not(target_os = "macos"))]
+    let hard_reload_shortcut = configured_shortcut(
+        &overrides,
+        "hard_reload",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyR),
+    );
+    let print = configured_shortcut(
+        &overrides,
+        "print",
+        Shortcut::new(Some(PRIMARY_MODIFIER), Code::KeyP),
+    );
+    let screenshot = configured_shortcut(
+        &overrides,
+        "screenshot",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyS),
+    );
+    let compact_mode = configured_shortcut(
+        &overrides,
+        "compact_mode",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::SHIFT), Code::KeyM),
+    );
+    let minimize_all_shortcut = configured_shortcut(
+        &overrides,
+        "minimize_all",
+        Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::ALT), Code::KeyM),
+    );
+    let show_all_shortcut = configured_shortcut(
+        &overrides,
+        "show_all",
+        Shortcut::new(
+            Some(PRIMARY_MODIFIER | Modifiers::ALT | Modifiers::SHIFT),
+            Code::KeyM,
+        ),
+    );
+
+    // Kiosk mode leaves out anything that opens other windows or leaves the app
+    let kiosk = app.state::<ConfigState>().kiosk_enabled();
+    let kiosk_blocked = [
+        new_window_shortcut,
+        open_settings_shortcut,
+        open_in_browser_shortcut,
+        quick_chat,
+        always_on_top,
+        print,
+        screenshot,
+        compact_mode,
+        minimize_all_shortcut,
+        show_all_shortcut,
+    ];
+
+    // Summoning Onyx has to work from any app
+    let global = [(show_app, "show_app"), (quick_chat, "quick_chat")];
+
+    let mut in_app = vec![
+        (new_chat, "new_chat"),
+        (back, "back"),
+        (forward, "forward"),
+        (new_window_shortcut, "new_window"),
+        (open_settings_shortcut, "open_settings"),
+        (zoom_in, "zoom_in"),
+        (zoom_out, "zoom_out"),
+        (reset_zoom, "reset_zoom"),
+        (find, "find"),
+        (open_in_browser_shortcut, "open_in_browser"),
+        (always_on_top, "always_on_top"),
+        (go_home_shortcut, "go_home"),
+        (print, "print"),
+        (screenshot, "screenshot"),
+        (compact_mode, "compact_mode"),
+        (minimize_all_shortcut, "minimize_all"),
+        (show_all_shortcut, "show_all"),
+    ];
+
+    // Avoid hijacking the system-wide Cmd+R on macOS.
+    #[cfg(not(target_os = "macos"))]
+    in_app.extend([(reload, "reload"), (hard_reload_shortcut, "hard_reload")]);
+
+    // Only exists in developer mode
+    if app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .devtools_enabled()
+    {
+        let devtools = configured_shortcut(
+            &overrides,
+            "devtools",
+            Shortcut::new(Some(PRIMARY_MODIFIER | Modifiers::ALT), Code::KeyI),
+        );
+        in_app.push((devtools, "devtools"));
+    }
+
+    in_app.retain(|(shortcut, _)| !(kiosk && kiosk_blocked.contains(shortcut)));
+    for (shortcut, action) in global {
+        if kiosk && kiosk_blocked.contains(&shortcut) {
+            continue;
+        }
+        let app_handle = app.clone();
+        app.global_shortcut()
+            .on_shortcut(shortcut, move |_app, _shortcut, event| {
+                if event.state() == ShortcutState::Pressed {
+                    run_shortcut_action(&app_handle, action);
+                }
+            })?;
+    }
+
+    // The in-app shortcuts wait for an Onyx window to be focused
+    let state = app.state::<AppShortcutsState>();
+    *state.shortcuts.lock().unwrap() = in_app;
+    *state.registered.lock().unwrap() = false;
+    refresh_app_shortcuts(app);
+
+    // The way out of kiosk mode, since there's no menu or tray Quit
+    if kiosk {
+        let exit_kiosk = configured_shortcut(
+            &overrides,
+            "exit_kiosk",
+            Shortcut::new(
+                Some(PRIMARY_MODIFIER | Modifiers::ALT | Modifiers::SHIFT),
+                Code::KeyK,
+            ),
+        );
+        let app_handle = app.clone();
+        app.global_shortcut()
+            .on_shortcut(exit_kiosk, move |_app, _shortcut, event| {
+                if event.state() != ShortcutState::Pressed {
+                    return;
+                }
+                info!("Exit shortcut pressed; quitting kiosk mode");
+                *app_handle.state::<QuitState>().confirmed.lock().unwrap() = true;
+                app_handle.exit(0);
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Shortcuts that act on Onyx itself. They're registered with the OS only
+/// while an Onyx window is focused, so keys like Cmd+F or Cmd+P still reach
+/// other apps.
+struct AppShortcutsState {
+    shortcuts: Mutex<Vec<(Shortcut, &'static str)>>,
+    registered: Mutex<bool>,
+}
+
+/// Register the in-app shortcuts if an Onyx window is focused, and
+/// unregister them if not
+fn refresh_app_shortcuts(app: &AppHandle) {
+    let focused = app
+        .webview_windows()
+        .values()
+        .any(|window| window.is_focused().unwrap_or(false));
+    // Locks are released before registering, which may wait on the main thread
+    let shortcuts = {
+        let state = app.state::<AppShortcutsState>();
+        let mut registered = state.registered.lock().unwrap();
+        if *registered == focused {
+            return;
+        }
+        *registered = focused;
+        state.shortcuts.lock().unwrap().clone()
+    };
+
+    for (shortcut, action) in shortcuts {
+        if !focused {
+            let _ = app.global_shortcut().unregister(shortcut);
+            continue;
+        }
+        let app_handle = app.clone();
+        let result = app
+            .global_shortcut()
+            .on_shortcut(shortcut, move |_app, _shortcut, event| {
+                // Handlers fire on release too; toggles would undo themselves
+                if event.state() == ShortcutState::Pressed {
+                    run_shortcut_action(&app_handle, action);
+                }
+            });
+        if let Err(e) = result {
+            warn!("Failed to register shortcut for '{}': {}", action, e);
+        }
+    }
+}
+
+/// Run the shortcut action named `action` (a key of the `shortcuts` config)
+fn run_shortcut_action(app: &AppHandle, action: &str) {
+    match action {
+        "new_chat" => trigger_new_chat(app),
+        "reload" | "hard_reload" | "back" | "forward" => {
+            let Some(window) = app.get_webview_window("main") else {
+                return;
+            };
+            match action {
+                "reload" => {
+                    let _ = window.eval("window.location.reload()");
+                }
+                "hard_reload" => {
+                    if let Err(e) = hard_reload(window) {
+                        error!("{}", e);
+                    }
+                }
+                "back" => {
+                    let _ = window.eval("window.history.back()");
+                }
+                _ => {
+                    let _ = window.eval("window.history.forward()");
+                }
+            }
+        }
+        "open_settings" => open_settings(app),
+        "new_window" => trigger_new_window(app),
+        "show_app" => toggle_main_window(app),
+        "zoom_in" => adjust_zoom_level(app, ZOOM_STEP),
+        "zoom_out" => adjust_zoom_level(app, -ZOOM_STEP),
+        "reset_zoom" => reset_focused_zoom_level(app),
+        "find" => {
+            if let Some(window) =
+                focused_window(app).filter(|window| window.label() != SETTINGS_WINDOW_LABEL)
+            {
+                open_find_bar(&window);
+            }
+        }
+        "open_in_browser" => {
+            if let Some(window) = focused_window(app) {
+                open_page_in_browser(app, &window);
+            }
+        }
+        "quick_chat" => show_quick_chat_window(app),
+        "always_on_top" => {
+            if let Some(window) = focused_window(app) {
+                if let Err(e) = toggle_always_on_top(app.clone(), window) {
+                    error!("{}", e);
+                }
+            }
+        }
+        "go_home" => {
+            if let Some(window) =
+                focused_window(app).filter(|window| window.label() != SETTINGS_WINDOW_LABEL)
+            {
+                if let Err(e) = go_home(window) {
+                    error!("Failed to go home: {}", e);
+                }
+            }
+        }
+        "print" => print_focused_page(app),
+        "screenshot" => capture_focused_window(app),
+        "compact_mode" => {
+            if let Some(window) = focused_window(app).filter(|window| is_app_window(window.label()))
+            {
+                if let Err(e) = toggle_compact(app.clone(), window) {
+                    error!("{}", e);
+                }
+            }
+        }
+        "minimize_all" => minimize_all(app.clone()),
+        "show_all" => show_all(app.clone()),
+        "devtools" => {
+            if let Some(window) = focused_window(app) {
+                if let Err(e) = toggle_devtools(window, app.state::<ConfigState>()) {
+                    error!("{}", e);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Re-register every global shortcut from the current config, so edits to
+/// `shortcuts` apply without a restart
+#[tauri::command]
+fn reload_shortcuts(app: AppHandle) -> Result<(), String> {
+    app.global_shortcut()
+        .unregister_all()
+        .map_err(|e| format!("Failed to unregister shortcuts: {}", e))?;
+    setup_shortcuts(&app).map_err(|e| format!("Failed to register shortcuts: {}", e))?;
+    info!("Reloaded global shortcuts");
+    Ok(())
+}
+
+// ============================================================================
+// Menu Setup
+// ============================================================================
+
+/// Find a top-level submenu by title, along with its position in the menu bar
+fn find_submenu(menu: &Menu<Wry>, title: &str) -> tauri::Result<Option<(usize, Submenu<Wry>)>> {
+    Ok(menu
+        .items()?
+        .into_iter()
+        .enumerate()
+        .find_map(|(index, item)| {
+            item.as_submenu()
+                .filter(|submenu| submenu.text().ok().as_deref() == Some(title))
+                .cloned()
+                .map(|submenu| (index, submenu))
+        }))
+}
+
+/// Swap the default menu's predefined Quit item for one that exits through
+/// `RunEvent::ExitRequested`, where `confirm_quit` is handled. The native item
+/// terminates the app without that event.
+fn replace_quit_menu_item(app: &AppHandle, menu: &Menu<Wry>) -> tauri::Result<()> {
+    for submenu in menu
+        .items()?
+        .into_iter()
+        .filter_map(|item| item.as_submenu().cloned())
+    {
+        let quit = submenu.items()?.into_iter().enumerate().find(|(_, item)| {
+            item.as_predefined_menuitem()
+                .and_then(|item| item.text().ok())
+                .is_some_and(|text| text.starts_with("Quit"))
+        });
+
+        if let Some((position, item)) = quit {
+            let text = item
+                .as_predefined_menuitem()
+                .and_then(|item| item.text().ok())
+                .unwrap_or_else(|| "Quit".to_string());
+            submenu.remove(&item)?;
+            submenu.insert(
+                &MenuItem::with_id(app, QUIT_MENU_ID, text, true, Some("CmdOrCtrl+Q"))?,
+                position,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether quitting now needs the user's confirmation
+fn should_confirm_quit(app: &AppHandle) -> bool {
+    if *app.state::<QuitState>().confirmed.lock().unwrap() {
+        return false;
+    }
+
+    let confirm_quit = app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .confirm_quit;
+    let open_windows = app
+        .webview_windows()
+        .keys()
+        .filter(|label| is_app_window(label))
+        .count();
+
+    confirm_quit && open_windows > 1
+}
+
+/// Set once the user agrees to quit, so the retried exit goes through, and
+/// once state has been flushed, so it's only written once
+struct QuitState {
+    confirmed: Mutex<bool>,
+    flushed: Mutex<bool>,
+    /// Set while the main window is rebuilt, so its closing doesn't quit the
+    /// app when it's the only window
+    recreating_main: Mutex<bool>,
+}
+
+/// Build the main window. It's built here instead of from tauri.conf.json so
+/// config-driven options like the User-Agent apply to it; window-state
+/// restores its saved size afterwards.
+fn build_main_window(app: &AppHandle) -> tauri::Result<Option<WebviewWindow>> {
+    let Some(window_config) = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|window| window.label == "main")
+    else {
+        return Ok(None);
+    };
+
+    let state = app.state::<ConfigState>();
+    let sizes = state.window_sizes();
+    let builder = WebviewWindowBuilder::from_config(app, window_config)?
+        .title(state.window_title())
+        .inner_size(sizes.width, sizes.height)
+        .min_inner_size(sizes.min_width, sizes.min_height)
+        .transparent(!state.vibrancy_disabled());
+    let user_agent = state.user_agent();
+    let proxy_url = state.proxy_url();
+    if let Some(proxy_url) = &proxy_url {
+        info!("Using proxy {}", redact_url_credentials(proxy_url));
+    }
+    let builder = apply_drag_drop(builder, state.native_drag_drop());
+    apply_download_handler(apply_proxy(
+        apply_user_agent(builder, user_agent.as_deref()),
+        proxy_url,
+    ))
+    .build()
+    .map(Some)
+}
+
+/// Apply the theme, zoom, and window options to a newly built main window
+/// and focus it
+fn setup_main_window(app: &AppHandle, window: &WebviewWindow) {
+    let state = app.state::<ConfigState>();
+    // Vibrancy for a translucent glass look, matched to the theme
+    apply_window_theme(window);
+
+    let _ = window.set_zoom(state.zoom_level());
+    apply_webview_tls_settings(window, &state.config.read().unwrap());
+    #[cfg(target_os = "linux")]
+    apply_webview_basic_auth(window);
+
+    if state.kiosk_enabled() {
+        let _ = window.set_fullscreen(true);
+    } else if state.always_on_top() {
+        let _ = apply_always_on_top(app, window, true);
+    }
+    let _ = window.set_focus();
+}
+
+/// Rebuild the main window so options a webview only reads when it's
+/// created, like the proxy, take effect without restarting the app. It comes
+/// back on the page it was showing, and window-state restores its size and
+/// position.
+async fn recreate_main_window(app: &AppHandle) -> Result<(), String> {
+    let Some(main) = app.get_webview_window("main") else {
+        return Ok(());
+    };
+    let url = main
+        .url()
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"));
+
+    *app.state::<QuitState>().recreating_main.lock().unwrap() = true;
+    if let Err(e) = main.destroy() {
+        *app.state::<QuitState>().recreating_main.lock().unwrap() = false;
+        return Err(format!("Failed to close main window: {}", e));
+    }
+    // The label can't be reused until the old window is gone
+    for _ in 0..50 {
+        if app.get_webview_window("main").is_none() {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    // Queued behind the old window's `Destroyed` handling, so that can't
+    // clear state belonging to the new one
+    let handle = app.clone();
+    app.run_on_main_thread(move || {
+        match build_main_window(&handle) {
+            Ok(Some(window)) => {
+                setup_main_window(&handle, &window);
+                if let Some(url) = url {
+                    let _ = window.navigate(url);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("Failed to recreate main window: {}", e),
+        }
+        *handle.state::<QuitState>().recreating_main.lock().unwrap() = false;
+    })
+    .map_err(|e| format!("Failed to recreate main window: {}", e))
+}
+
+/// Write the state kept in memory (config, recent chats, and the open
+/// windows) to disk before quitting. Only the first call per run does
+/// anything. The writes happen on a helper thread so a stuck disk or keychain
+/// can't hold up quitting past `SHUTDOWN_FLUSH_TIMEOUT`.
+fn flush_app_state(app: &AppHandle) {
+    {
+        let state = app.state::<QuitState>();
+        let mut flushed = state.flushed.lock().unwrap();
+        if *flushed {
+            return;
+        }
+        *flushed = true;
+    }
+
+    for window in app.webview_windows().values() {
+        if is_app_window(window.label()) {
+            remember_window_geometry(app, window);
+        }
+    }
+    let config = {
+        let state = app.state::<ConfigState>();
+        let initialized = *state.config_initialized.read().unwrap();
+        // Before first-run setup there's no config file to write
+        initialized.then(|| state.config.read().unwrap().clone())
+    };
+    let chat_ids = app
+        .state::<RecentChatsState>()
+        .chat_ids
+        .read()
+        .unwrap()
+        .clone();
+    let session = capture_window_session(app);
+
+    let (done_tx, done_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        if let Some(config) = config {
+            if let Err(e) = save_config(&config) {
+                error!("Failed to save config: {}", e);
+            }
+        }
+        if let Err(e) = save_recent_chats(&chat_ids) {
+            error!("Failed to save recent chats: {}", e);
+        }
+        if let Err(e) = save_window_session(&session) {
+            error!("Failed to save window session: {}", e);
+        }
+        let _ = done_tx.send(());
+    });
+
+    if done_rx.recv_timeout(SHUTDOWN_FLUSH_TIMEOUT).is_err() {
+        warn!(
+            "Saving app state took longer than {:?}; quitting anyway",
+            SHUTDOWN_FLUSH_TIMEOUT
+        );
+    }
+}
+
+/// Ask before quitting with several windows open, then exit if confirmed
+fn confirm_quit(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let confirmed = confirm_dialog(
+            &app,
+            "Quit Onyx?",
+            "You have several Onyx windows open. Any unsent messages will be lost.",
+            MessageDialogKind::Warning,
+            "Quit",
+        )
+        .await
+        .unwrap_or_else(|e| {
+            error!("{}", e);
+            false
+        });
+
+        if confirmed {
+            *app.state::<QuitState>().confirmed.lock().unwrap() = true;
+            app.exit(0);
+        }
+    });
+}
+
+fn setup_app_menu(app: &AppHandle) -> tauri::Result<()> {
+    let menu = app.menu().unwrap_or(Menu::default(app)?);
+    replace_quit_menu_item(app, &menu)?;
+
+    let new_chat_item = MenuItem::with_id(app, "new_chat", "New Chat", true, Some("CmdOrCtrl+N"))?;
+    let new_window_item = MenuItem::with_id(
+        app,
+        "new_window",
+        "New Window",
+        true,
+        Some("CmdOrCtrl+Shift+N"),
+    )?;
+    let settings_item = MenuItem::with_id(app, "open_settings", "Settings...", true, Some("CmdOrCtrl+Comma"))?;
+    let open_in_browser_item =
+        MenuItem::with_id(app, "open_in_browser", "Open in Browser", true, None::<&str>)?;
+    // No accelerator: the global shortcut already handles Cmd/Ctrl+P
+    let print_item = MenuItem::with_id(
+        app,
+        "print_to_pdf",
+        "Print / Save as PDF…",
+        true,
+        None::<&str>,
+    )?;
+    // No accelerator: the global shortcut already handles Cmd/Ctrl+Shift+S
+    let screenshot_item =
+        MenuItem::with_id(app, "capture_window", "Save Screenshot", true, None::<&str>)?;
+    let docs_item = MenuItem::with_id(app, "open_docs", "Onyx Documentation", true, None::<&str>)?;
+    let check_updates_item = MenuItem::with_id(
+        app,
+        "check_for_updates",
+        "Check for Updates…",
+        true,
+        None::<&str>,
+    )?;
+    let open_logs_item = MenuItem::with_id(
+        app,
+        "open_logs_directory",
+        "Open Logs Folder",
+        true,
+        None::<&str>,
+    )?;
+    let about_item = MenuItem::with_id(app, "about_onyx", "About Onyx", true, None::<&str>)?;
+    let clear_data_item = MenuItem::with_id(
+        app,
+        "clear_session_data",
+        "Log Out / Clear Data...",
+        true,
+        None::<&str>,
+    )?;
+    let reset_geometry_item = MenuItem::with_id(
+        app,
+        "reset_window_geometry",
+        "Reset Window Size & Position",
+        true,
+        None::<&str>,
+    )?;
+    // No accelerator: the global shortcut already handles Cmd/Ctrl+Shift+T,
+    // and a second binding would toggle twice
+    let always_on_top_item = CheckMenuItem::with_id(
+        app,
+        ALWAYS_ON_TOP_MENU_ID,
+        "Always on Top",
+        true,
+        false,
+        None::<&str>,
+    )?;
+    // No accelerator: the global shortcut already handles Cmd/Ctrl+Shift+M
+    let compact_item = MenuItem::with_id(
+        app,
+        COMPACT_MENU_ID,
+        "Toggle Compact Mode",
+        true,
+        None::<&str>,
+    )?;
+
+    if let Some(file_menu) = menu
+        .items()?
+        .into_iter()
+        .filter_map(|item| item.as_submenu().cloned())
+        .find(|submenu| submenu.text().ok().as_deref() == Some("File"))
+    {
+        file_menu.insert_items(
+            &[
+                &new_chat_item,
+                &new_window_item,
+                &open_in_browser_item,
+                &print_item,
+                &screenshot_item,
+                &settings_item,
+            ],
+            0,
+        )?;
+    } else {
+        let file_menu = SubmenuBuilder::new(app, "File")
+            .items(&[
+                &new_chat_item,
+                &new_window_item,
+                &open_in_browser_item,
+                &print_item,
+                &screenshot_item,
+                &settings_item,
+                &PredefinedMenuItem::close_window(app, None)?,
+            ])
+            .build()?;
+        menu.prepend(&file_menu)?;
+    }
+
+    // Edit: the native clipboard items, which Windows/Linux don't get by default
+    if find_submenu(&menu, "Edit")?.is_none() {
+        let edit_menu = SubmenuBuilder::new(app, "Edit")
+            .items(&[
+                &PredefinedMenuItem::undo(app, None)?,
+                &PredefinedMenuItem::redo(app, None)?,
+                &PredefinedMenuItem::separator(app)?,
+                &PredefinedMenuItem::cut(app, None)?,
+                &PredefinedMenuItem::copy(app, None)?,
+                &PredefinedMenuItem::paste(app, None)?,
+                &PredefinedMenuItem::select_all(app, None)?,
+            ])
+            .build()?;
+        let position = find_submenu(&menu, "File")?.map_or(0, |(index, _)| index + 1);
+        menu.insert(&edit_menu, position)?;
+    }
+
+    if let Some((_, edit_menu)) = find_submenu(&menu, "Edit")? {
+        let copy_url_item = MenuItem::with_id(
+            app,
+            "copy_current_url",
+            "Copy Current URL",
+            true,
+            None::<&str>,
+        )?;
+        let spellcheck_item = CheckMenuItem::with_id(
+            app,
+            SPELLCHECK_MENU_ID,
+            "Check Spelling While Typing",
+            true,
+            app.state::<ConfigState>().config.read().unwrap().spellcheck,
+            None::<&str>,
+        )?;
+        edit_menu.append_items(&[
+            &PredefinedMenuItem::separator(app)?,
+            &copy_url_item,
+            &spellcheck_item,
+        ])?;
+    }
+
+    // View: reload, zoom, and full screen
+    let reload_item = MenuItem::with_id(app, "reload_page", "Reload", true, Some("CmdOrCtrl+R"))?;
+    let hard_reload_item = MenuItem::with_id(
+        app,
+        "hard_reload",
+        "Reload Ignoring Cache",
+        true,
+        Some("CmdOrCtrl+Shift+R"),
+    )?;
+    let zoom_in_item = MenuItem::with_id(app, "zoom_in", "Zoom In", true, Some("CmdOrCtrl+="))?;
+    let zoom_out_item = MenuItem::with_id(app, "zoom_out", "Zoom Out", true, Some("CmdOrCtrl+-"))?;
+    let reset_zoom_item =
+        MenuItem::with_id(app, "reset_zoom", "Actual Size", true, Some("CmdOrCtrl+0"))?;
+    let view_items: [&dyn IsMenuItem<Wry>; 7] = [
+        &reload_item,
+        &hard_reload_item,
+        &PredefinedMenuItem::separator(app)?,
+        &zoom_in_item,
+        &zoom_out_item,
+        &reset_zoom_item,
+        &PredefinedMenuItem::separator(app)?,
+    ];
+
+    if let Some((_, view_menu)) = find_submenu(&menu, "View")? {
+        // macOS's default View menu already has a native full screen item
+        view_menu.insert_items(&view_items, 0)?;
+    } else {
+        let fullscreen_item = MenuItem::with_id(
+            app,
+            "toggle_fullscreen",
+            "Toggle Full Screen",
+            true,
+            Some("F11"),
+        )?;
+        let view_menu = SubmenuBuilder::new(app, "View")
+            .items(&view_items)
+            .item(&fullscreen_item)
+            .build()?;
+        let position = find_submenu(&menu, "Edit")?.map_or(0, |(index, _)| index + 1);
+        menu.insert(&view_menu, position)?;
+    }
+
+    if let Some(window_menu) = menu
+        .get(WINDOW_SUBMENU_ID)
+        .and_then(|item| item.as_submenu().cloned())
+    {
+        window_menu.append_items(&[
+            &PredefinedMenuItem::separator(app)?,
+            &always_on_top_item,
+            &compact_item,
+            &reset_geometry_item,
+        ])?;
+    } else {
+        let window_menu = SubmenuBuilder::with_id(app, WINDOW_SUBMENU_ID, "Window")
+            .item(&always_on_top_item)
+            .item(&compact_item)
+            .item(&reset_geometry_item)
+            .build()?;
+        menu.append(&window_menu)?;
+    }
+
+    if let Some(help_menu) = menu
+        .get(HELP_SUBMENU_ID)
+        .and_then(|item| item.as_submenu().cloned())
+    {
+        help_menu.append_items(&[
+            &docs_item,
+            &check_updates_item,
+            &open_logs_item,
+            &about_item,
+            &PredefinedMenuItem::separator(app)?,
+            &clear_data_item,
+        ])?;
+    } else {
+        let help_menu = SubmenuBuilder::with_id(app, HELP_SUBMENU_ID, "Help")
+            .item(&docs_item)
+            .item(&check_updates_item)
+            .item(&open_logs_item)
+            .item(&about_item)
+            .separator()
+            .item(&clear_data_item)
+            .build()?;
+        menu.append(&help_menu)?;
+    }
+
+    app.set_menu(menu)?;
+    Ok(())
+}
+
+fn build_tray_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let open_app = MenuItem::with_id(
+        app,
+        TRAY_MENU_OPEN_APP_ID,
+        "Open Onyx",
+        true,
+        Some("CmdOrCtrl+Shift+Space"),
+    )?;
+    let open_chat = MenuItem::with_id(
+        app,
+        TRAY_MENU_OPEN_CHAT_ID,
+        "Open Chat Window",
+        true,
+        None::<&str>,
+    )?;
+    let minimize_all = MenuItem::with_id(
+        app,
+        TRAY_MENU_MINIMIZE_ALL_ID,
+        "Minimize All Windows",
+        true,
+        None::<&str>,
+    )?;
+    let show_all = MenuItem::with_id(
+        app,
+        TRAY_MENU_SHOW_ALL_ID,
+        "Show All Windows",
+        true,
+        None::<&str>,
+    )?;
+    // Only ever seen while the icon is shown, so it's always checked
+    let show_in_menu_bar = CheckMenuItem::with_id(
+        app,
+        TRAY_MENU_SHOW_IN_BAR_ID,
+        "Show in Menu Bar",
+        true,
+        true,
+        None::<&str>,
+    )?;
+    let restart = MenuItem::with_id(
+        app,
+        TRAY_MENU_RESTART_ID,
+        "Restart Onyx",
+        true,
+        None::<&str>,
+    )?;
+    let connection_info = MenuItem::with_id(
+        app,
+        TRAY_MENU_CONNECTION_INFO_ID,
+        "Connection Info",
+        true,
+        None::<&str>,
+    )?;
+    // Not the predefined quit item, so quitting goes through `confirm_quit`
+    let quit = MenuItem::with_id(app, TRAY_MENU_QUIT_ID, "Quit Onyx", true, None::<&str>)?;
+
+    let recent_chat_ids = app
+        .state::<RecentChatsState>()
+        .chat_ids
+        .read()
+        .unwrap()
+        .clone();
+    let mut recent_chats = SubmenuBuilder::new(app, "Recent Chats");
+    if recent_chat_ids.is_empty() {
+        recent_chats =
+            recent_chats.item(&MenuItem::new(app, "No Recent Chats", false, None::<&str>)?);
+    }
+    for chat_id in &recent_chat_ids {
+        let label: String = chat_id.chars().take(8).collect();
+        recent_chats = recent_chats.item(&MenuItem::with_id(
+            app,
+            format!("{}{}", TRAY_MENU_RECENT_CHAT_PREFIX, chat_id),
+            format!("Chat {}", label),
+            true,
+            None::<&str>,
+        )?);
+    }
+    let recent_chats = recent_chats.build()?;
+
+    let (servers, active_server) = {
+        let state = app.state::<ConfigState>();
+        let config = state.config.read().unwrap();
+        // A session override from the environment means no profile is in use
+        let active_server = config
+            .server_url_override
+            .is_none()
+            .then_some(config.active_server);
+        (config.servers.clone(), active_server)
+    };
+    let mut switch_server = SubmenuBuilder::new(app, "Switch Server");
+    for (index, profile) in servers.iter().enumerate() {
+        switch_server = switch_server.item(&CheckMenuItem::with_id(
+            app,
+            format!("{}{}", TRAY_MENU_SERVER_PREFIX, index),
+            &profile.name,
+            true,
+            active_server == Some(index),
+            None::<&str>,
+        )?);
+    }
+    let switch_server = switch_server.build()?;
+
+    let mut new_window_on = SubmenuBuilder::new(app, "New Window On");
+    for (index, profile) in servers.iter().enumerate() {
+        new_window_on = new_window_on.item(&MenuItem::with_id(
+            app,
+            format!("{}{}", TRAY_MENU_NEW_WINDOW_SERVER_PREFIX, index),
+            &profile.name,
+            true,
+            None::<&str>,
+        )?);
+    }
+    let new_window_on = new_window_on.build()?;
+
+    let menu = MenuBuilder::new(app)
+        .item(&open_app)
+        .item(&open_chat)
+        .item(&recent_chats)
+        .item(&switch_server)
+        .item(&new_window_on)
+        .item(&restart)
+        .separator()
+        .item(&minimize_all)
+        .item(&show_all)
+        .separator()
+        .item(&connection_info)
+        .item(&show_in_menu_bar);
+    // Kiosk mode is left with its exit shortcut instead
+    let kiosk = app.state::<ConfigState>().kiosk_enabled();
+    if kiosk {
+        menu.build()
+    } else {
+        menu.separator().item(&quit).build()
+    }
+}
+
+fn handle_tray_menu_event(app: &AppHandle, id: &str) {
+    match id {
+        TRAY_MENU_OPEN_APP_ID => {
+            focus_main_window(app);
+        }
+        TRAY_MENU_OPEN_CHAT_ID => {
+            focus_main_window(app);
+            trigger_new_chat(app);
+        }
+        TRAY_MENU_QUIT_ID => {
+            app.exit(0);
+        }
+        TRAY_MENU_MINIMIZE_ALL_ID => minimize_all(app.clone()),
+        TRAY_MENU_SHOW_ALL_ID => show_all(app.clone()),
+        TRAY_MENU_RESTART_ID => {
+            if let Err(e) = restart_app(app) {
+                error!("Failed to restart: {}", e);
+            }
+        }
+        TRAY_MENU_CONNECTION_INFO_ID => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move { show_connection_info(&app).await });
+        }
+        TRAY_MENU_SHOW_IN_BAR_ID => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Err(e) = hide_tray_icon(&app).await {
+                    error!("Failed to hide tray icon: {}", e);
+                }
+                // Undo the checkmark the click toggled, in case the icon stays
+                refresh_tray_menu(&app);
+            });
+        }
+        _ => {
+            if let Some(chat_id) = id.strip_prefix(TRAY_MENU_RECENT_CHAT_PREFIX) {
+                show_chat(app, chat_id);
+            } else if let Some(index) = id.strip_prefix(TRAY_MENU_NEW_WINDOW_SERVER_PREFIX) {
+                open_window_for_profile(app, index);
+            } else if let Some(index) = id.strip_prefix(TRAY_MENU_SERVER_PREFIX) {
+                let result = index
+                    .parse()
+                    .map_err(|_| format!("Invalid server index '{}'", index))
+                    .and_then(|index| switch_server(app.clone(), index));
+                if let Err(e) = result {
+                    error!("Failed to switch server: {}", e);
+                    // Undo the checkmark the click toggled
+                    refresh_tray_menu(app);
+                }
+            }
+        }
+    }
+}
+
+/// Open a new window on the server profile at `index` (as text, from a menu id)
+fn open_window_for_profile(app: &AppHandle, index: &str) {
+    let profile = index.parse::<usize>().ok().and_then(|index| {
+        app.state::<ConfigState>()
+            .config
+            .read()
+            .unwrap()
+            .servers
+            .get(index)
+            .cloned()
+    });
+    let Some(profile) = profile else {
+        error!("Invalid server index '{}'", index);
+        return;
+    };
+
+    let app = app.clone();
+    // Build off the event thread; creating windows synchronously can deadlock on Windows
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<ConfigState>();
+        if let Err(e) = new_window_for_server(app.clone(), state, profile.url).await {
+            error!("Failed to open window on {}: {}", profile.name, e);
+        }
+    });
+}
+
+/// Hide the tray icon after the user confirms, explaining how to get it back
+async fn hide_tray_icon(app: &AppHandle) -> Result<(), String> {
+    let confirmed = confirm_dialog(
+        app,
+        "Hide the Menu Bar Icon?",
+        "You can show it again from Settings. While it's hidden, Cmd/Ctrl+Shift+Space still brings Onyx forward.",
+        MessageDialogKind::Info,
+        "Hide",
+    )
+    .await?;
+    if !confirmed {
+        return Ok(());
+    }
+
+    set_show_tray_icon(app.clone(), app.state::<ConfigState>(), false).map(|_| ())
+}
+
+/// Apply `tray_left_click` to the existing tray icon
+fn apply_tray_left_click(app: &AppHandle, left_click: TrayLeftClick) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(e) = tray.set_show_menu_on_left_click(left_click == TrayLeftClick::Menu) {
+            error!("Failed to update tray click behavior: {}", e);
+        }
+    }
+}
+
+/// Show or hide the tray icon
+fn apply_tray_icon_visibility(app: &AppHandle, visible: bool) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(e) = tray.set_visible(visible) {
+            error!("Failed to update tray icon visibility: {}", e);
+        }
+    }
+}
+
+/// Rebuild the tray menu so dynamic sections (e.g. recent chats) stay current
+fn refresh_tray_menu(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+
+    match build_tray_menu(app) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => error!("Failed to rebuild tray menu: {}", e),
+    }
+}
+
+/// The tray icon, with a dot when there are unread messages, dimmed to half
+/// opacity when the server is offline
+fn tray_icon_image(app: &AppHandle, offline: bool, unread: bool) -> Option<Image<'static>> {
+    let bytes = if unread {
+        TRAY_ICON_UNREAD_BYTES
+    } else {
+        TRAY_ICON_BYTES
+    };
+    let icon = Image::from_bytes(bytes).ok().or_else(|| {
+        app.default_window_icon()
+            .map(|icon| icon.clone().to_owned())
+    })?;
+
+    if !offline {
+        return Some(icon);
+    }
+
+    let mut rgba = icon.rgba().to_vec();
+    for pixel in rgba.chunks_exact_mut(4) {
+        pixel[3] /= 2;
+    }
+    Some(Image::new_owned(rgba, icon.width(), icon.height()))
+}
+
+fn setup_tray_icon(app: &AppHandle) -> tauri::Result<()> {
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID).tooltip("Onyx");
+
+    if let Some(icon) = tray_icon_image(app, false, false) {
+        builder = builder.icon(icon);
+
+        #[cfg(target_os = "macos")]
+        {
+            builder = builder.icon_as_template(true);
+        }
+    }
+
+    if let Ok(menu) = build_tray_menu(app) {
+        builder = builder.menu(&menu);
+    }
+    let tray_left_click = app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .tray_left_click;
+
+    builder
+        .show_menu_on_left_click(tray_left_click == TrayLeftClick::Menu)
+        .on_tray_icon_event(|tray, event| {
+            // Only a left click shows the app, so one click never opens both
+            // the window and the menu
+            let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            else {
+                return;
+            };
+            let app = tray.app_handle();
+            let left_click = app
+                .state::<ConfigState>()
+                .config
+                .read()
+                .unwrap()
+                .tray_left_click;
+            if left_click == TrayLeftClick::Show {
+                focus_main_window(app);
+            }
+        })
+        .on_menu_event(|app, event| handle_tray_menu_event(app, event.id().as_ref()))
+        .build(app)?;
+
+    let show_tray_icon = app
+        .state::<ConfigState>()
+        .config
+        .read()
+        .unwrap()
+        .show_tray_icon;
+    apply_tray_icon_visibility(app, show_tray_icon);
+
+    Ok(())
+}
+
+// ============================================================================
+// Connection Status
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ConnectionStatus {
+    Unknown,
+    Connected,
+    Offline,
+}
+
+// Latest server reachability, refreshed by a background task while the app runs
+struct ConnectionState {
+    status: RwLock<ConnectionStatus>,
+    monitor: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Get the latest known server connection status
 #[tauri::command]
-fn go_forward(window: tauri::WebviewWindow) {
-    let _ = window.eval("window.history.forward()");
+fn get_connection_status(state: tauri::State<ConnectionState>) -> ConnectionStatus {
+    *state.status.read().unwrap()
+}
+
+/// Record a new status and reflect it in the tray tooltip and icon
+fn set_connection_status(app: &AppHandle, status: ConnectionStatus) {
+    {
+        let state = app.state::<ConnectionState>();
+        let mut current = state.status.write().unwrap();
+        if *current == status {
+            return;
+        }
+        *current = status;
+    }
+
+    if status == ConnectionStatus::Connected {
+        leave_offline_pages(app);
+    }
+
+    refresh_tray_status(app);
+}
+
+/// Tray tooltip for a connection status and unread count. Being offline
+/// matters more than unread messages, which can't arrive anyway.
+fn tray_tooltip(status: ConnectionStatus, unread: u32) -> String {
+    match status {
+        ConnectionStatus::Offline => "Onyx — Offline".to_string(),
+        _ if unread > 0 => format!("Onyx — {} unread", unread),
+        ConnectionStatus::Connected | ConnectionStatus::Unknown => "Onyx".to_string(),
+    }
+}
+
+/// Reflect the connection status and unread count in the tray tooltip and icon
+fn refresh_tray_status(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    let status = *app.state::<ConnectionState>().status.read().unwrap();
+    let unread = *app.state::<UnreadState>().count.lock().unwrap();
+
+    let _ = tray.set_tooltip(Some(tray_tooltip(status, unread)));
+
+    if let Some(icon) = tray_icon_image(app, status == ConnectionStatus::Offline, unread > 0) {
+        let _ = tray.set_icon(Some(icon));
+
+        #[cfg(target_os = "macos")]
+        let _ = tray.set_icon_as_template(true);
+    }
+}
+
+/// Check the active server on demand and report the result in the tray
+/// tooltip and a notification
+async fn show_connection_info(app: &AppHandle) {
+    let server_url = app.state::<ConfigState>().server_url();
+    let result = check_server_reachable(app.state::<ConfigState>(), None).await;
+
+    let (tooltip, body) = match &result {
+        Ok(latency_ms) => {
+            set_connection_status(app, ConnectionStatus::Connected);
+            (
+                format!("Onyx — Connected ({} ms)", latency_ms),
+                format!("{} responded in {} ms", server_url, latency_ms),
+            )
+        }
+        Err(e) => {
+            set_connection_status(app, ConnectionStatus::Offline);
+            (
+                "Onyx — Server unreachable".to_string(),
+                format!("Server unreachable: {}", e),
+            )
+        }
+    };
+
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_tooltip(Some(tooltip));
+    }
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title("Connection Info")
+        .body(body)
+        .show()
+    {
+        error!("Failed to show notification: {}", e);
+    }
+}
+
+/// URL of a page bundled with the app, as the webview serves it
+fn bundled_page_url(page: &str) -> Url {
+    #[cfg(target_os = "windows")]
+    let base = "http://tauri.localhost/";
+    #[cfg(not(target_os = "windows"))]
+    let base = "tauri://localhost/";
+
+    Url::parse(base)
+        .and_then(|base| base.join(page))
+        .expect("bundled page URL is valid")
+}
+
+fn is_offline_page(url: &Url) -> bool {
+    let offline = bundled_page_url(OFFLINE_PAGE);
+    url.origin() == offline.origin() && url.path() == offline.path()
+}
+
+/// After a page from the server finishes loading, make sure it actually
+/// loaded: the webview has no load-error hook, so if the server doesn't answer
+/// a health check, swap the browser's failure page for the bundled offline page.
+fn check_server_page_loaded(webview: &Webview, url: &Url) {
+    let server_url = window_server_url(webview.app_handle(), webview.label());
+    let (client, basic_auth) = {
+        let config = webview.state::<ConfigState>().config.read().unwrap();
+        (http_client(&config), config.basic_auth_for(&server_url))
+    };
+    // The connection status tracks the active server only
+    let pinned = is_pinned_to_server(webview.app_handle(), webview.label());
+
+    let on_server = Url::parse(&server_url)
+        .map(|server| hosts_match(url, &server))
+        .unwrap_or(false);
+    let connected =
+        *webview.state::<ConnectionState>().status.read().unwrap() == ConnectionStatus::Connected;
+    if !on_server || (connected && !pinned) {
+        return;
+    }
+
+    let webview = webview.clone();
+    tauri::async_runtime::spawn(async move {
+        let reachable = match client {
+            Ok(client) => ping_server(&client, &server_url, basic_auth).await.is_ok(),
+            Err(_) => false,
+        };
+
+        if pinned {
+            if !reachable {
+                let _ = webview.navigate(bundled_page_url(OFFLINE_PAGE));
+            }
+        } else if reachable {
+            set_connection_status(webview.app_handle(), ConnectionStatus::Connected);
+        } else {
+            set_connection_status(webview.app_handle(), ConnectionStatus::Offline);
+            let _ = webview.navigate(bundled_page_url(OFFLINE_PAGE));
+        }
+    });
+}
+
+/// Send every window showing the offline page back to the server
+fn leave_offline_pages(app: &AppHandle) {
+    let Ok(server_url) = parse_server_url(&app.state::<ConfigState>().server_url()) else {
+        return;
+    };
+
+    for window in app.webview_windows().values() {
+        // Windows on other servers recover through their own retries
+        if is_pinned_to_server(app, window.label()) {
+            continue;
+        }
+
+        let offline = window
+            .url()
+            .map(|url| is_offline_page(&url))
+            .unwrap_or(false);
+        if offline {
+            let _ = window.navigate(server_url.clone());
+        }
+    }
 }
 
-/// Open a new window
+/// Retry the window's server from the offline page: returns to the app if it
+/// answers, or the error describing why it's still unreachable
 #[tauri::command]
-async fn new_window(app: AppHandle, state: tauri::State<'_, ConfigState>) -> Result<(), String> {
-    let server_url = state.config.read().unwrap().server_url.clone();
-    let window_label = format!("onyx-{}", uuid::Uuid::new_v4());
+async fn retry_connection(app: AppHandle, window: tauri::WebviewWindow) -> Result<(), String> {
+    let server_url = window_server_url(&app, window.label());
+    let (client, basic_auth) = {
+        let config = app.state::<ConfigState>().config.read().unwrap();
+        (http_client(&config)?, config.basic_auth_for(&server_url))
+    };
 
-    let builder = WebviewWindowBuilder::new(
-        &app,
-        &window_label,
-        WebviewUrl::External(
-            server_url
-                .parse()
-                .map_err(|e| format!("Invalid URL: {}", e))?,
-        ),
-    )
-    .title("Onyx")
-    .inner_size(1200.0, 800.0)
-    .min_inner_size(800.0, 600.0)
-    .transparent(true);
+    // Windows on other servers don't affect the active server's status
+    if is_pinned_to_server(&app, window.label()) {
+        ping_server(&client, &server_url, basic_auth).await?;
+        return window
+            .navigate(parse_server_url(&server_url)?)
+            .map_err(|e| e.to_string());
+    }
 
-    #[cfg(target_os = "macos")]
-    let builder = builder
-        .title_bar_style(tauri::TitleBarStyle::Overlay)
-        .hidden_title(true);
+    match ping_server(&client, &server_url, basic_auth).await {
+        Ok(_) => {
+            set_connection_status(&app, ConnectionStatus::Connected);
+            // Already done by the status change unless it was Connected before
+            leave_offline_pages(&app);
+            Ok(())
+        }
+        Err(e) => {
+            set_connection_status(&app, ConnectionStatus::Offline);
+            Err(e)
+        }
+    }
+}
 
-    #[cfg(target_os = "linux")]
-    let builder = builder.background_color(tauri::window::Color(0x1a, 0x1a, 0x2e, 0xff));
+/// Periodically ping the configured server and update the tray status
+fn start_connection_monitor(app: &AppHandle) {
+    let handle = app.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        loop {
+            let (server_url, interval_secs, client, basic_auth) = {
+                let state = handle.state::<ConfigState>();
+                let config = state.config.read().unwrap();
+                let server_url = config.effective_server_url().to_string();
+                let basic_auth = config.basic_auth_for(&server_url);
+                (
+                    server_url,
+                    config.connection_check_interval_secs,
+                    http_client(&config),
+                    basic_auth,
+                )
+            };
 
-    #[cfg(target_os = "macos")]
-    {
-        let window = builder.build().map_err(|e| e.to_string())?;
-        // Apply vibrancy effect and inject titlebar
-        let _ = apply_vibrancy(&window, NSVisualEffectMaterial::Sidebar, None, None);
-        inject_titlebar(window.clone());
-    }
+            let status = match client {
+                Ok(client) => match ping_server(&client, &server_url, basic_auth).await {
+                    Ok(_) => ConnectionStatus::Connected,
+                    Err(_) => ConnectionStatus::Offline,
+                },
+                Err(e) => {
+                    error!("{}", e);
+                    ConnectionStatus::Offline
+                }
+            };
+            set_connection_status(&handle, status);
 
-    #[cfg(not(target_os = "macos"))]
-    {
-        let _window = builder.build().map_err(|e| e.to_string())?;
+            let interval_secs = interval_secs.max(MIN_CONNECTION_CHECK_INTERVAL_SECS);
+            sleep(Duration::from_secs(interval_secs)).await;
+        }
+    });
+
+    *app.state::<ConnectionState>().monitor.lock().unwrap() = Some(task);
+}
+
+fn stop_connection_monitor(app: &AppHandle) {
+    let state = app.state::<ConnectionState>();
+    let task = state.monitor.lock().unwrap().take();
+    if let Some(task) = task {
+        task.abort();
     }
+}
 
-    Ok(())
+// ============================================================================
+// Session Keep-Alive
+// ============================================================================
+
+// The periodic task that keeps the web session warm, when enabled
+struct KeepAliveState {
+    task: Mutex<Option<JoinHandle<()>>>,
 }
 
-/// Reset config to defaults
-#[tauri::command]
-fn reset_config(state: tauri::State<ConfigState>) -> Result<(), String> {
-    let mut config = state.config.write().unwrap();
-    *config = AppConfig::default();
-    save_config(&config)?;
-    *state.config_initialized.write().unwrap() = true;
-    Ok(())
+/// Touch the server from a window showing it, so the request carries the
+/// session cookies the server's idle timeout looks at
+fn ping_session(app: &AppHandle) {
+    let Ok(server) = parse_server_url(&app.state::<ConfigState>().server_url()) else {
+        return;
+    };
+
+    let mut windows: Vec<WebviewWindow> = app
+        .webview_windows()
+        .into_values()
+        .filter(|window| is_app_window(window.label()))
+        .filter(|window| {
+            window
+                .url()
+                .is_ok_and(|url| url.origin() == server.origin())
+        })
+        .collect();
+    windows.sort_by_key(|window| window.label() != "main");
+
+    if let Some(window) = windows.first() {
+        let script = format!(
+            "fetch('{}', {{ credentials: 'include', cache: 'no-store' }}).catch(() => {{}});",
+            KEEP_ALIVE_PATH
+        );
+        if let Err(e) = window.eval(&script) {
+            warn!("Failed to send keep-alive request: {}", e);
+        }
+    }
 }
 
-#[cfg(target_os = "macos")]
-fn inject_titlebar(window: WebviewWindow) {
-    let script = TITLEBAR_SCRIPT.to_string();
-    tauri::async_runtime::spawn(async move {
-        // Keep trying for a few seconds to survive navigations and slow loads
-        let delays = [0u64, 200, 600, 1200, 2000, 4000, 6000, 8000, 10000];
-        for delay in delays {
-            if delay > 0 {
-                sleep(Duration::from_millis(delay)).await;
+/// Start pinging the server every `keep_alive_minutes`, skipping pings while
+/// it's known to be offline. Stops on its own if the setting is removed.
+fn start_keep_alive(app: &AppHandle) {
+    let keep_alive_minutes = |app: &AppHandle| {
+        app.state::<ConfigState>()
+            .config
+            .read()
+            .unwrap()
+            .keep_alive_minutes
+    };
+    if keep_alive_minutes(app).is_none() {
+        return;
+    }
+
+    let handle = app.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        while let Some(minutes) = keep_alive_minutes(&handle) {
+            let minutes = minutes.max(MIN_KEEP_ALIVE_MINUTES);
+            sleep(Duration::from_secs(minutes * 60)).await;
+
+            let status = *handle.state::<ConnectionState>().status.read().unwrap();
+            if status != ConnectionStatus::Offline {
+                ping_session(&handle);
             }
-            let _ = window.eval(&script);
         }
     });
+
+    *app.state::<KeepAliveState>().task.lock().unwrap() = Some(task);
 }
 
-/// Start dragging the window
-#[tauri::command]
-async fn start_drag_window(window: tauri::Window) -> Result<(), String> {
-    window.start_dragging().map_err(|e| e.to_string())
+fn stop_keep_alive(app: &AppHandle) {
+    let task = app.state::<KeepAliveState>().task.lock().unwrap().take();
+    if let Some(task) = task {
+        task.abort();
+    }
 }
 
 // ============================================================================
-// Shortcuts Setup
+// Auto-Reload
 // ============================================================================
 
-fn setup_shortcuts(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    let new_chat = Shortcut::new(Some(Modifiers::SUPER), Code::KeyN);
-    let reload = Shortcut::new(Some(Modifiers::SUPER), Code::KeyR);
-    let back = Shortcut::new(Some(Modifiers::SUPER), Code::BracketLeft);
-    let forward = Shortcut::new(Some(Modifiers::SUPER), Code::BracketRight);
-    let new_window_shortcut = Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::KeyN);
-    let show_app = Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::Space);
-    let open_settings_shortcut = Shortcut::new(Some(Modifiers::SUPER), Code::Comma);
+// The periodic task that reloads the main window, when enabled
+struct AutoReloadState {
+    task: Mutex<Option<JoinHandle<()>>>,
+}
 
-    let app_handle = app.clone();
+/// Whether the user has left the keyboard and mouse alone long enough for a
+/// reload not to interrupt them. If idle time can't be read, assume so.
+fn quiet_for_reload() -> bool {
+    system_idle_seconds().map_or(true, |seconds| seconds >= AUTO_RELOAD_QUIET_SECS)
+}
 
-    // Avoid hijacking the system-wide Cmd+R on macOS.
-    #[cfg(target_os = "macos")]
-    let shortcuts = [
-        new_chat,
-        back,
-        forward,
-        new_window_shortcut,
-        show_app,
-        open_settings_shortcut,
-    ];
+/// Reload the main window every `auto_reload_minutes`, waiting for a pause in
+/// input and skipping reloads while the server is offline. Stops on its own if
+/// the setting is removed.
+fn start_auto_reload(app: &AppHandle) {
+    let auto_reload_minutes = |app: &AppHandle| {
+        app.state::<ConfigState>()
+            .config
+            .read()
+            .unwrap()
+            .auto_reload_minutes
+    };
+    if auto_reload_minutes(app).is_none() {
+        return;
+    }
 
-    #[cfg(not(target_os = "macos"))]
-    let shortcuts = [
-        new_chat,
-        reload,
-        back,
-        forward,
-        new_window_shortcut,
-        show_app,
-        open_settings_shortcut,
-    ];
+    let handle = app.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        while let Some(minutes) = auto_reload_minutes(&handle) {
+            let minutes = minutes.max(MIN_AUTO_RELOAD_MINUTES);
+            sleep(Duration::from_secs(minutes * 60)).await;
 
-    app.global_shortcut().on_shortcuts(
-        shortcuts,
-        move |_app, shortcut, _event| {
-            if shortcut == &new_chat {
-                trigger_new_chat(&app_handle);
+            while !quiet_for_reload() {
+                sleep(IDLE_POLL_INTERVAL).await;
             }
 
-            if let Some(window) = app_handle.get_webview_window("main") {
-                if shortcut == &reload {
-                    let _ = window.eval("window.location.reload()");
-                } else if shortcut == &back {
-                    let _ = window.eval("window.history.back()");
-                } else if shortcut == &forward {
-                    let _ = window.eval("window.history.forward()");
-                } else if shortcut == &open_settings_shortcut {
-                    open_settings(&app_handle);
-                }
+            let status = *handle.state::<ConnectionState>().status.read().unwrap();
+            if status == ConnectionStatus::Offline {
+                continue;
             }
-
-            if shortcut == &new_window_shortcut {
-                trigger_new_window(&app_handle);
-            } else if shortcut == &show_app {
-                focus_main_window(&app_handle);
+            if let Some(window) = handle.get_webview_window("main") {
+                info!("Auto-reloading the main window");
+                reload_page(window);
             }
-        },
-    )?;
+        }
+    });
 
-    Ok(())
+    *app.state::<AutoReloadState>().task.lock().unwrap() = Some(task);
+}
+
+fn stop_auto_reload(app: &AppHandle) {
+    let task = app.state::<AutoReloadState>().task.lock().unwrap().take();
+    if let Some(task) = task {
+        task.abort();
+    }
 }
 
 // ============================================================================
-// Menu Setup
+// Idle Detection
 // ============================================================================
 
-fn setup_app_menu(app: &AppHandle) -> tauri::Result<()> {
-    let menu = app.menu().unwrap_or(Menu::default(app)?);
+// Whether the user is currently idle, tracked by a background watcher
+struct IdleState {
+    idle: Mutex<bool>,
+    watcher: Mutex<Option<JoinHandle<()>>>,
+}
 
-    let new_chat_item = MenuItem::with_id(app, "new_chat", "New Chat", true, Some("CmdOrCtrl+N"))?;
-    let new_window_item = MenuItem::with_id(
-        app,
-        "new_window",
-        "New Window",
-        true,
-        Some("CmdOrCtrl+Shift+N"),
-    )?;
-    let settings_item = MenuItem::with_id(app, "open_settings", "Settings...", true, Some("CmdOrCtrl+Comma"))?;
-    let docs_item = MenuItem::with_id(app, "open_docs", "Onyx Documentation", true, None::<&str>)?;
+/// Seconds since the last keyboard or mouse input anywhere on the system
+fn system_idle_seconds() -> Result<u64, String> {
+    user_idle::UserIdle::get_time()
+        .map(|idle| idle.as_seconds())
+        .map_err(|e| format!("Failed to read idle time: {}", e))
+}
 
-    if let Some(file_menu) = menu
-        .items()?
-        .into_iter()
-        .filter_map(|item| item.as_submenu().cloned())
-        .find(|submenu| submenu.text().ok().as_deref() == Some("File"))
-    {
-        file_menu.insert_items(&[&new_chat_item, &new_window_item, &settings_item], 0)?;
-    } else {
-        let file_menu = SubmenuBuilder::new(app, "File")
-            .items(&[
-                &new_chat_item,
-                &new_window_item,
-                &settings_item,
-                &PredefinedMenuItem::close_window(app, None)?,
-            ])
-            .build()?;
-        menu.prepend(&file_menu)?;
-    }
+/// Get how long the user has been idle, in seconds
+#[tauri::command]
+fn get_idle_time() -> Result<u64, String> {
+    system_idle_seconds()
+}
 
-    if let Some(help_menu) = menu
-        .get(HELP_SUBMENU_ID)
-        .and_then(|item| item.as_submenu().cloned())
-    {
-        help_menu.append(&docs_item)?;
-    } else {
-        let help_menu = SubmenuBuilder::with_id(app, HELP_SUBMENU_ID, "Help")
-            .item(&docs_item)
-            .build()?;
-        menu.append(&help_menu)?;
+/// Poll the system idle time and emit `user-idle` once it passes the
+/// configured threshold, then `user-active` when input resumes
+fn start_idle_watcher(app: &AppHandle) {
+    let handle = app.clone();
+    let task = tauri::async_runtime::spawn(async move {
+        loop {
+            sleep(IDLE_POLL_INTERVAL).await;
+
+            let idle_seconds = match system_idle_seconds() {
+                Ok(seconds) => seconds,
+                Err(e) => {
+                    warn!("{}; stopping idle detection", e);
+                    return;
+                }
+            };
+            let threshold = handle
+                .state::<ConfigState>()
+                .config
+                .read()
+                .unwrap()
+                .idle_threshold_seconds
+                .max(MIN_IDLE_THRESHOLD_SECS);
+
+            let idle = idle_seconds >= threshold;
+            {
+                let state = handle.state::<IdleState>();
+                let mut current = state.idle.lock().unwrap();
+                if *current == idle {
+                    continue;
+                }
+                *current = idle;
+            }
+
+            let result = if idle {
+                handle.emit(USER_IDLE_EVENT, idle_seconds)
+            } else {
+                handle.emit(USER_ACTIVE_EVENT, ())
+            };
+            if let Err(e) = result {
+                error!("Failed to emit idle status: {}", e);
+            }
+        }
+    });
+
+    *app.state::<IdleState>().watcher.lock().unwrap() = Some(task);
+}
+
+fn stop_idle_watcher(app: &AppHandle) {
+    let task = app.state::<IdleState>().watcher.lock().unwrap().take();
+    if let Some(task) = task {
+        task.abort();
     }
+}
 
-    app.set_menu(menu)?;
-    Ok(())
+// ============================================================================
+// Config Hot Reload
+// ============================================================================
+
+// Watches config.json for edits made outside the app; dropping the watcher
+// stops its thread
+struct ConfigWatcherState {
+    watcher: Mutex<Option<RecommendedWatcher>>,
 }
 
-fn build_tray_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
-    let open_app = MenuItem::with_id(
-        app,
-        TRAY_MENU_OPEN_APP_ID,
-        "Open Onyx",
-        true,
-        Some("CmdOrCtrl+Shift+Space"),
-    )?;
-    let open_chat = MenuItem::with_id(
-        app,
-        TRAY_MENU_OPEN_CHAT_ID,
-        "Open Chat Window",
-        true,
-        None::<&str>,
-    )?;
-    let show_in_menu_bar = CheckMenuItem::with_id(
-        app,
-        TRAY_MENU_SHOW_IN_BAR_ID,
-        "Show in Menu Bar",
-        true,
-        true,
-        None::<&str>,
-    )?;
-    // Keep it visible/pinned without letting users uncheck (avoids orphaning the tray)
-    let _ = show_in_menu_bar.set_enabled(false);
-    let quit = PredefinedMenuItem::quit(app, Some("Quit Onyx"))?;
+/// Re-read config.json after an external edit and apply it. Invalid files are
+/// logged and the current config is kept. The app's own saves read back
+/// unchanged and are ignored.
+fn reload_config_from_disk(app: &AppHandle) {
+    let config_path = get_config_path();
+    let contents = match fs::read_to_string(&config_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            // Deleting the file keeps the running config; it's re-saved later
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to read {}: {}", config_path.display(), e);
+            }
+            return;
+        }
+    };
+    let (config, changed) = match parse_config(&contents) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            warn!("Ignoring invalid edit to {}: {}", config_path.display(), e);
+            return;
+        }
+    };
 
-    MenuBuilder::new(app)
-        .item(&open_app)
-        .item(&open_chat)
-        .separator()
-        .item(&show_in_menu_bar)
-        .separator()
-        .item(&quit)
-        .build()
+    // e.g. a password pasted into the file, which belongs in the keychain
+    if changed {
+        if let Err(e) = save_config(&config) {
+            error!("Failed to save reloaded config: {}", e);
+        }
+    }
+    if replace_config(app, config) {
+        info!("Reloaded config from {}", config_path.display());
+    }
 }
 
-fn handle_tray_menu_event(app: &AppHandle, id: &str) {
-    match id {
-        TRAY_MENU_OPEN_APP_ID => {
-            focus_main_window(app);
+/// Swap in `config`, keeping any session server override and managed
+/// settings, and apply the settings that can change while running. Returns
+/// false if nothing changed.
+fn replace_config(app: &AppHandle, config: AppConfig) -> bool {
+    let mut config = apply_managed_config(&config, managed_config());
+    let state = app.state::<ConfigState>();
+    let previous_url = state.server_url();
+    let previous = {
+        let mut current = state.config.write().unwrap();
+        config.server_url_override = current.server_url_override.clone();
+        if config_value_with_secrets(&config).ok() == config_value_with_secrets(&current).ok() {
+            return false;
         }
-        TRAY_MENU_OPEN_CHAT_ID => {
-            focus_main_window(app);
-            trigger_new_chat(app);
+        std::mem::replace(&mut *current, config.clone())
+    };
+    *state.config_initialized.write().unwrap() = true;
+
+    emit_config_changed(app, &config);
+    apply_tray_icon_visibility(app, config.show_tray_icon);
+    apply_tray_left_click(app, config.tray_left_click);
+    apply_spellcheck(app, config.spellcheck);
+    refresh_spellcheck_menu(app, config.spellcheck);
+    if config.launch_at_login != previous.launch_at_login {
+        if let Err(e) = apply_launch_at_login(app, config.launch_at_login) {
+            error!("{}", e);
         }
-        TRAY_MENU_QUIT_ID => {
-            app.exit(0);
+    }
+    if config.keep_alive_minutes != previous.keep_alive_minutes {
+        stop_keep_alive(app);
+        start_keep_alive(app);
+    }
+    if config.auto_reload_minutes != previous.auto_reload_minutes {
+        stop_auto_reload(app);
+        start_auto_reload(app);
+    }
+    if config.always_on_top != previous.always_on_top {
+        for window in app.webview_windows().values() {
+            if is_app_window(window.label()) {
+                let _ = apply_always_on_top(app, window, config.always_on_top);
+            }
         }
-        TRAY_MENU_SHOW_IN_BAR_ID => {
-            // No-op for now; the item stays checked/disabled to indicate it's pinned.
+    }
+    if config.shortcuts != previous.shortcuts {
+        if let Err(e) = reload_shortcuts(app.clone()) {
+            error!("{}", e);
+        }
+    }
+    if previous.needs_restart_from(&config) {
+        info!("Some config changes apply after a restart");
+    }
+
+    move_windows_to_server(app, &previous_url, &state.server_url());
+    refresh_tray_menu(app);
+    true
+}
+
+/// Watch the config directory (editors often replace the file rather than
+/// write it in place) and reload once a burst of changes settles
+fn start_config_watcher(app: &AppHandle) {
+    let config_path = get_config_path();
+    let Some(config_dir) = config_path.parent().map(Path::to_path_buf) else {
+        return;
+    };
+    if let Err(e) = fs::create_dir_all(&config_dir) {
+        warn!("Failed to create config dir: {}", e);
+        return;
+    }
+
+    let (tx, rx) = mpsc::channel::<notify::Result<notify::Event>>();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Failed to watch config file: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&config_dir, RecursiveMode::NonRecursive) {
+        warn!("Failed to watch {}: {}", config_dir.display(), e);
+        return;
+    }
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let touches_config = |event: &notify::Result<notify::Event>| {
+            event
+                .as_ref()
+                .is_ok_and(|event| event.paths.iter().any(|path| path == &config_path))
+        };
+
+        // Ends once the watcher is dropped and the channel closes
+        while let Ok(event) = rx.recv() {
+            if !touches_config(&event) {
+                continue;
+            }
+            loop {
+                match rx.recv_timeout(CONFIG_RELOAD_DEBOUNCE) {
+                    Ok(_) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            reload_config_from_disk(&handle);
+        }
+    });
+
+    *app.state::<ConfigWatcherState>().watcher.lock().unwrap() = Some(watcher);
+}
+
+fn stop_config_watcher(app: &AppHandle) {
+    let watcher = app
+        .state::<ConfigWatcherState>()
+        .watcher
+        .lock()
+        .unwrap()
+        .take();
+    drop(watcher);
+}
+
+// ============================================================================
+// Dock Menu (macOS)
+// ============================================================================
+
+// Tauri has no Dock menu API, so the menu is built with AppKit directly and
+// served from the app delegate's `applicationDockMenu:`.
+#[cfg(target_os = "macos")]
+static DOCK_APP_HANDLE: OnceLock<AppHandle> = OnceLock::new();
+#[cfg(target_os = "macos")]
+static DOCK_MENU: OnceLock<usize> = OnceLock::new();
+
+#[cfg(target_os = "macos")]
+extern "C-unwind" fn dock_new_chat(_this: &AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    if let Some(app) = DOCK_APP_HANDLE.get() {
+        focus_main_window(app);
+        trigger_new_chat(app);
+    }
+}
+
+#[cfg(target_os = "macos")]
+extern "C-unwind" fn dock_new_window(_this: &AnyObject, _cmd: Sel, _sender: *mut AnyObject) {
+    if let Some(app) = DOCK_APP_HANDLE.get() {
+        trigger_new_window(app);
+    }
+}
+
+#[cfg(target_os = "macos")]
+extern "C-unwind" fn application_dock_menu(
+    _this: &AnyObject,
+    _cmd: Sel,
+    _sender: *mut AnyObject,
+) -> *mut AnyObject {
+    DOCK_MENU
+        .get()
+        .map_or(std::ptr::null_mut(), |menu| *menu as *mut AnyObject)
+}
+
+#[cfg(target_os = "macos")]
+fn setup_dock_menu(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    if DOCK_APP_HANDLE.set(app.clone()).is_err() {
+        return Ok(());
+    }
+
+    // SAFETY: called once from `setup` on the main thread; every object created
+    // here is intentionally leaked so it lives as long as the app.
+    unsafe {
+        let mut target_class = ClassBuilder::new(c"OnyxDockMenuTarget", class!(NSObject))
+            .ok_or("Dock menu target class is already registered")?;
+        target_class.add_method(
+            sel!(newChat:),
+            dock_new_chat as extern "C-unwind" fn(_, _, _),
+        );
+        target_class.add_method(
+            sel!(newWindow:),
+            dock_new_window as extern "C-unwind" fn(_, _, _),
+        );
+        let target_class = target_class.register();
+        let target: Retained<AnyObject> = msg_send![target_class, new];
+
+        let menu: Retained<AnyObject> = msg_send![class!(NSMenu), new];
+        let no_key = NSString::from_str("");
+        for (title, action) in [
+            ("New Chat", sel!(newChat:)),
+            ("New Window", sel!(newWindow:)),
+        ] {
+            let title = NSString::from_str(title);
+            let item: Allocated<AnyObject> = msg_send![class!(NSMenuItem), alloc];
+            let item: Retained<AnyObject> = msg_send![
+                item,
+                initWithTitle: &*title,
+                action: action,
+                keyEquivalent: &*no_key
+            ];
+            let _: () = msg_send![&item, setTarget: &*target];
+            let _: () = msg_send![&menu, addItem: &*item];
         }
-        _ => {}
+
+        let ns_app: *mut AnyObject = msg_send![class!(NSApplication), sharedApplication];
+        let delegate: *mut AnyObject = msg_send![ns_app, delegate];
+        let delegate = delegate.as_ref().ok_or("NSApplication has no delegate")?;
+
+        let _ = Retained::into_raw(target);
+        let _ = DOCK_MENU.set(Retained::into_raw(menu) as usize);
+
+        let dock_menu_imp = std::mem::transmute::<
+            extern "C-unwind" fn(&AnyObject, Sel, *mut AnyObject) -> *mut AnyObject,
+            Imp,
+        >(application_dock_menu);
+        objc2::ffi::class_addMethod(
+            delegate.class() as *const AnyClass as *mut AnyClass,
+            sel!(applicationDockMenu:),
+            dock_menu_imp,
+            c"@@:@".as_ptr(),
+        );
     }
-}
 
-fn setup_tray_icon(app: &AppHandle) -> tauri::Result<()> {
-    let mut builder = TrayIconBuilder::with_id(TRAY_ID).tooltip("Onyx");
+    Ok(())
+}
 
-    let tray_icon = Image::from_bytes(TRAY_ICON_BYTES)
-        .ok()
-        .or_else(|| app.default_window_icon().cloned());
+// ============================================================================
+// Deep Links
+// ============================================================================
 
-    if let Some(icon) = tray_icon {
-        builder = builder.icon(icon);
+fn setup_deep_links(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    // Installed bundles register the scheme themselves; dev builds need it at runtime
+    #[cfg(any(target_os = "linux", all(debug_assertions, target_os = "windows")))]
+    app.deep_link().register_all()?;
 
-        #[cfg(target_os = "macos")]
-        {
-            builder = builder.icon_as_template(true);
+    let app_handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle_deep_link(&app_handle, url.as_str());
         }
-    }
+    });
 
-    if let Ok(menu) = build_tray_menu(app) {
-        builder = builder.menu(&menu);
+    // Cold start: the app may have been launched by the link itself
+    if let Some(urls) = app.deep_link().get_current()? {
+        for url in urls {
+            handle_deep_link(app, url.as_str());
+        }
     }
 
-    builder
-        .on_tray_icon_event(|tray, event| {
-            if let TrayIconEvent::Click { .. } = event {
-                focus_main_window(tray.app_handle());
-            }
-        })
-        .on_menu_event(|app, event| handle_tray_menu_event(app, event.id().as_ref()))
-        .build(app)?;
-
     Ok(())
 }
 
@@ -661,85 +7336,1128 @@ fn setup_tray_icon(app: &AppHandle) -> tauri::Result<()> {
 // ============================================================================
 
 fn main() {
+    let cli = match parse_cli_args(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Some(path) = cli.config_path {
+        let _ = CONFIG_PATH_OVERRIDE.set(path);
+    }
+    let _ = KIOSK_ARG.set(cli.kiosk);
+
+    let (_log_guard, log_level) = init_logging();
+    info!("Starting Onyx {}", env!("CARGO_PKG_VERSION"));
+    let config_dir = resolve_config_dir();
+    if standard_config_dir().as_ref() != Some(&config_dir) {
+        warn!(
+            "Standard config directory unavailable; using {}",
+            config_dir.display()
+        );
+    }
+
     // Load config at startup
-    let (config, config_initialized) = load_config();
+    let (config, config_initialized) = load_config(cli.server_url);
+    if let Err(e) = log_level.reload(parse_log_level(&config.log_level)) {
+        warn!("Failed to apply log_level: {}", e);
+    }
 
     tauri::Builder::default()
+        // Must be registered first so a second launch exits before doing any setup
+        .plugin(tauri_plugin_single_instance::init(|app, argv, _cwd| {
+            focus_main_window(app);
+
+            // On Windows/Linux a clicked onyx:// link arrives as an argument to a new process
+            let prefix = format!("{}://", DEEP_LINK_SCHEME);
+            for arg in argv.iter().filter(|arg| arg.starts_with(&prefix)) {
+                handle_deep_link(app, arg);
+            }
+        }))
+        .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .plugin(tauri_plugin_window_state::Builder::default().build())
+        .plugin(tauri_plugin_autostart::init(
+            MacosLauncher::LaunchAgent,
+            None,
+        ))
+        .plugin(
+            tauri::plugin::Builder::<Wry>::new("external-links")
+                .on_navigation(|webview, url| {
+                    let server_url = window_server_url(webview.app_handle(), webview.label());
+                    let in_app = is_in_app_url(url, &server_url);
+                    if allow_sso_navigation(webview, url, &server_url) || in_app {
+                        return true;
+                    }
+
+                    // Kiosk mode keeps users on the server, without a browser either
+                    if webview.app_handle().state::<ConfigState>().kiosk_enabled() {
+                        info!(
+                            "Blocked off-domain navigation in kiosk mode: {}",
+                            redact_url_credentials(url)
+                        );
+                        return false;
+                    }
+
+                    // Off-domain links open in the system browser instead of replacing the app
+                    open_url(url.as_str());
+                    false
+                })
+                .build(),
+        )
         .manage(ConfigState {
             config: RwLock::new(config),
             config_initialized: RwLock::new(config_initialized),
-            app_base_url: RwLock::new(None),
+        })
+        .manage(RecentChatsState {
+            chat_ids: RwLock::new(load_recent_chats()),
+        })
+        .manage(NotificationState {
+            pending_chat_path: Mutex::new(None),
+        })
+        .manage(QuitState {
+            confirmed: Mutex::new(false),
+            flushed: Mutex::new(false),
+            recreating_main: Mutex::new(false),
+        })
+        .manage(LastFocusedWindowState {
+            label: Mutex::new(None),
+        })
+        .manage(NavigationState {
+            windows: Mutex::new(HashMap::new()),
+        })
+        .manage(SsoState {
+            windows: Mutex::new(HashMap::new()),
+        })
+        .manage(PageTitleState {
+            titles: Mutex::new(HashMap::new()),
+        })
+        .manage(AlwaysOnTopState {
+            labels: Mutex::new(HashSet::new()),
+        })
+        .manage(CompactState {
+            windows: Mutex::new(HashMap::new()),
+        })
+        .manage(CustomCssState {
+            cache: Mutex::new(None),
+        })
+        .manage(AppShortcutsState {
+            shortcuts: Mutex::new(Vec::new()),
+            registered: Mutex::new(false),
+        })
+        .manage(UnreadState {
+            count: Mutex::new(0),
+        })
+        .manage(ConnectionState {
+            status: RwLock::new(ConnectionStatus::Unknown),
+            monitor: Mutex::new(None),
+        })
+        .manage(WindowServerState {
+            servers: RwLock::new(HashMap::new()),
+        })
+        .manage(ConfigWatcherState {
+            watcher: Mutex::new(None),
+        })
+        .manage(KeepAliveState {
+            task: Mutex::new(None),
+        })
+        .manage(AutoReloadState {
+            task: Mutex::new(None),
+        })
+        .manage(IdleState {
+            idle: Mutex::new(false),
+            watcher: Mutex::new(None),
         })
         .invoke_handler(tauri::generate_handler![
             get_server_url,
             get_bootstrap_state,
             set_server_url,
+            get_recent_servers,
+            check_server_reachable,
             get_config_path_cmd,
             open_config_file,
             open_config_directory,
+            open_logs_directory,
+            get_app_info,
+            get_managed_keys,
             navigate_to,
+            navigate_relative,
+            open_chat,
+            toggle_devtools,
             reload_page,
+            hard_reload,
             go_back,
             go_forward,
+            go_home,
+            can_go_back,
+            can_go_forward,
+            set_navigation_state,
+            set_page_title,
+            get_theme,
             new_window,
+            new_window_for_server,
+            list_windows,
+            focus_window,
+            close_window,
+            minimize_all,
+            show_all,
             reset_config,
-            start_drag_window
+            get_config,
+            update_config,
+            export_config,
+            import_config,
+            start_drag_window,
+            reset_window_geometry,
+            find_in_page,
+            stop_find,
+            open_in_browser,
+            copy_current_url,
+            print_to_pdf,
+            capture_window,
+            show_notification,
+            set_badge_count,
+            set_unread_count,
+            set_always_on_top,
+            toggle_always_on_top,
+            toggle_compact,
+            reload_shortcuts,
+            clear_session_data,
+            clear_session,
+            clear_session_for_url,
+            switch_server,
+            list_profiles,
+            add_profile,
+            remove_profile,
+            switch_profile,
+            relaunch_app,
+            set_proxy,
+            clear_proxy,
+            retry_connection,
+            check_for_updates,
+            get_zoom_level,
+            set_zoom_level,
+            reset_zoom,
+            open_settings_window,
+            close_settings_window,
+            get_launch_at_login,
+            set_launch_at_login,
+            get_spellcheck,
+            set_spellcheck,
+            get_show_tray_icon,
+            set_show_tray_icon,
+            get_connection_status,
+            get_idle_time
         ])
+        .on_window_event(|window, event| {
+            if let WindowEvent::Focused(true) = event {
+                if is_app_window(window.label()) {
+                    *window
+                        .state::<LastFocusedWindowState>()
+                        .label
+                        .lock()
+                        .unwrap() = Some(window.label().to_string());
+                }
+                handle_notification_focus(window.app_handle());
+                // Looking at the app reads what was waiting
+                let _ = set_unread_count(window.app_handle().clone(), 0);
+                refresh_always_on_top_menu(window.app_handle(), window.label());
+            }
+
+            if let WindowEvent::ThemeChanged(_) = event {
+                apply_theme_to_all_windows(window.app_handle());
+            }
+
+            if let WindowEvent::DragDrop(DragDropEvent::Drop { paths, position }) = event {
+                if is_app_window(window.label()) || window.label() == QUICK_CHAT_WINDOW_LABEL {
+                    let scale = window.scale_factor().unwrap_or(1.0);
+                    let position = position.to_logical::<f64>(scale);
+                    forward_dropped_files(window, paths.clone(), (position.x, position.y));
+                }
+            }
+
+            if let WindowEvent::Destroyed = event {
+                if is_app_window(window.label()) {
+                    persist_window_session(window.app_handle(), Some(window.label()));
+                }
+                window
+                    .state::<AlwaysOnTopState>()
+                    .labels
+                    .lock()
+                    .unwrap()
+                    .remove(window.label());
+                window
+                    .state::<CompactState>()
+                    .windows
+                    .lock()
+                    .unwrap()
+                    .remove(window.label());
+                window
+                    .state::<NavigationState>()
+                    .windows
+                    .lock()
+                    .unwrap()
+                    .remove(window.label());
+                window
+                    .state::<PageTitleState>()
+                    .titles
+                    .lock()
+                    .unwrap()
+                    .remove(window.label());
+                window
+                    .state::<WindowServerState>()
+                    .servers
+                    .write()
+                    .unwrap()
+                    .remove(window.label());
+                window
+                    .state::<SsoState>()
+                    .windows
+                    .lock()
+                    .unwrap()
+                    .remove(window.label());
+            }
+
+            // The quick-chat window behaves like a launcher: dismiss on blur
+            if let WindowEvent::Focused(false) = event {
+                if window.label() == QUICK_CHAT_WINDOW_LABEL {
+                    let _ = window.hide();
+                }
+            }
+
+            if let WindowEvent::Focused(_) = event {
+                refresh_app_shortcuts(window.app_handle());
+            }
+
+            if let WindowEvent::CloseRequested { api, .. } = event {
+                if is_app_window(window.label()) {
+                    if let Some(window) = window.app_handle().get_webview_window(window.label()) {
+                        persist_window_geometry(window.app_handle(), &window);
+                    }
+                }
+
+                // Without a tray icon a hidden main window would be hard to find
+                let close_to_tray = {
+                    let state = window.state::<ConfigState>();
+                    let config = state.config.read().unwrap();
+                    config.close_to_tray && config.show_tray_icon
+                };
+
+                if close_to_tray && window.label() == "main" {
+                    api.prevent_close();
+                    let _ = window.hide();
+                }
+            }
+        })
         .on_menu_event(|app, event| match event.id().as_ref() {
             "open_docs" => open_docs(),
+            QUIT_MENU_ID => app.exit(0),
+            "check_for_updates" => {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = run_update_check(&app, true).await {
+                        error!("{}", e);
+                    }
+                });
+            }
+            "open_logs_directory" => {
+                if let Err(e) = open_logs_directory() {
+                    error!("{}", e);
+                }
+            }
+            "about_onyx" => show_about_dialog(app),
+            "copy_current_url" => {
+                if let Some(window) = focused_window(app) {
+                    if let Err(e) = copy_current_url(window, app.clone()) {
+                        error!("{}", e);
+                    }
+                }
+            }
+            "open_in_browser" => {
+                if let Some(window) = focused_window(app) {
+                    open_page_in_browser(app, &window);
+                }
+            }
             "new_chat" => trigger_new_chat(app),
             "new_window" => trigger_new_window(app),
             "open_settings" => open_settings(app),
+            "print_to_pdf" => print_focused_page(app),
+            "capture_window" => capture_focused_window(app),
+            "clear_session_data" => {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = clear_session_data(app).await {
+                        error!("{}", e);
+                    }
+                });
+            }
+            SPELLCHECK_MENU_ID => {
+                let enabled = !app.state::<ConfigState>().config.read().unwrap().spellcheck;
+                if let Err(e) = set_spellcheck(app.clone(), enabled) {
+                    error!("Failed to update spellcheck: {}", e);
+                    // Undo the checkmark the click toggled
+                    refresh_spellcheck_menu(app, !enabled);
+                }
+            }
+            ALWAYS_ON_TOP_MENU_ID => {
+                if let Some(window) = focused_window(app) {
+                    if let Err(e) = toggle_always_on_top(app.clone(), window) {
+                        error!("{}", e);
+                    }
+                }
+            }
+            COMPACT_MENU_ID => {
+                if let Some(window) = focused_window(app).filter(|w| is_app_window(w.label())) {
+                    if let Err(e) = toggle_compact(app.clone(), window) {
+                        error!("{}", e);
+                    }
+                }
+            }
+            "reset_window_geometry" => {
+                if let Some(window) = focused_window(app) {
+                    if let Err(e) = reset_window_to_default(&window) {
+                        error!("Failed to reset window geometry: {}", e);
+                    }
+                }
+            }
+            "reload_page" => {
+                if let Some(window) = focused_window(app) {
+                    reload_page(window);
+                }
+            }
+            "hard_reload" => {
+                if let Some(window) = focused_window(app) {
+                    if let Err(e) = hard_reload(window) {
+                        error!("{}", e);
+                    }
+                }
+            }
+            "zoom_in" => adjust_zoom_level(app, ZOOM_STEP),
+            "zoom_out" => adjust_zoom_level(app, -ZOOM_STEP),
+            "reset_zoom" => reset_focused_zoom_level(app),
+            "toggle_fullscreen" => {
+                if let Some(window) = focused_window(app) {
+                    let fullscreen = window.is_fullscreen().unwrap_or(false);
+                    let _ = window.set_fullscreen(!fullscreen);
+                }
+            }
             _ => {}
         })
         .setup(move |app| {
             let app_handle = app.handle();
 
+            build_main_window(app_handle)?;
+
             // Setup global shortcuts
             if let Err(e) = setup_shortcuts(&app_handle) {
-                eprintln!("Failed to setup shortcuts: {}", e);
+                error!("Failed to setup shortcuts: {}", e);
             }
 
-            if let Err(e) = setup_app_menu(&app_handle) {
-                eprintln!("Failed to setup menu: {}", e);
+            let kiosk = app.state::<ConfigState>().kiosk_enabled();
+            if kiosk {
+                info!("Starting in kiosk mode");
+                // Drops the default menu too, along with its Quit item
+                let _ = app_handle.remove_menu();
+            } else if let Err(e) = setup_app_menu(&app_handle) {
+                error!("Failed to setup menu: {}", e);
             }
 
             if let Err(e) = setup_tray_icon(&app_handle) {
-                eprintln!("Failed to setup tray icon: {}", e);
+                error!("Failed to setup tray icon: {}", e);
             }
 
-            // Setup main window with vibrancy effect
-            if let Some(window) = app.get_webview_window("main") {
-                // Apply vibrancy effect for translucent glass look
-                #[cfg(target_os = "macos")]
-                {
-                    let _ = apply_vibrancy(&window, NSVisualEffectMaterial::Sidebar, None, None);
-                }
+            #[cfg(target_os = "macos")]
+            if let Err(e) = setup_dock_menu(&app_handle) {
+                error!("Failed to setup dock menu: {}", e);
+            }
 
-                if let Ok(url) = window.url() {
-                    let mut base_url = url;
-                    base_url.set_query(None);
-                    base_url.set_fragment(None);
-                    base_url.set_path("/");
-                    *app.state::<ConfigState>().app_base_url.write().unwrap() = Some(base_url);
-                }
+            start_connection_monitor(&app_handle);
+            start_keep_alive(&app_handle);
+            start_auto_reload(&app_handle);
+            start_idle_watcher(&app_handle);
+            start_config_watcher(&app_handle);
 
-                #[cfg(target_os = "macos")]
-                inject_titlebar(window.clone());
+            let spellcheck = app.state::<ConfigState>().config.read().unwrap().spellcheck;
+            apply_spellcheck(&app_handle, spellcheck);
 
-                let _ = window.set_focus();
+            if let Err(e) = setup_deep_links(&app_handle) {
+                error!("Failed to setup deep links: {}", e);
+            }
+
+            // Keep the OS login item in sync with config (e.g. after a manual edit)
+            let launch_at_login = app
+                .state::<ConfigState>()
+                .config
+                .read()
+                .unwrap()
+                .launch_at_login;
+            if let Err(e) = apply_launch_at_login(&app_handle, launch_at_login) {
+                error!("{}", e);
+            }
+
+            if let Some(window) = app.get_webview_window("main") {
+                setup_main_window(app_handle, &window);
+            }
+
+            if app.state::<ConfigState>().restore_session() {
+                restore_window_session(&app_handle);
+            }
+
+            let auto_check_updates = app
+                .state::<ConfigState>()
+                .config
+                .read()
+                .unwrap()
+                .auto_check_updates;
+            if auto_check_updates {
+                let handle = app_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = run_update_check(&handle, false).await {
+                        warn!("{}", e);
+                    }
+                });
             }
 
             Ok(())
         })
-        .on_page_load(|_webview: &Webview, _payload: &PageLoadPayload| {
-            // Re-inject titlebar after every navigation/page load (macOS only)
+        .on_page_load(|webview: &Webview, payload: &PageLoadPayload| {
+            // Inject the titlebar once the new page's DOM is ready (macOS only);
+            // the script ignores repeat injections. The settings window keeps
+            // its native title bar and the quick-chat window has none.
             #[cfg(target_os = "macos")]
-            let _ = _webview.eval(TITLEBAR_SCRIPT);
+            if matches!(payload.event(), PageLoadEvent::Finished)
+                && webview.label() != SETTINGS_WINDOW_LABEL
+                && webview.label() != QUICK_CHAT_WINDOW_LABEL
+            {
+                let _ = webview.eval(TITLEBAR_SCRIPT);
+            }
+
+            // Keep the saved zoom across reloads and navigations
+            let server_url = window_server_url(webview.app_handle(), webview.label());
+            let zoom_level = webview
+                .app_handle()
+                .state::<ConfigState>()
+                .config
+                .read()
+                .unwrap()
+                .zoom_level_for(&server_url);
+            let _ = webview.set_zoom(zoom_level);
+
+            if let PageLoadEvent::Finished = payload.event() {
+                // Install back/forward tracking and find-in-page so they're
+                // ready before they're needed, and apply the user's custom.css
+                if webview.label() != SETTINGS_WINDOW_LABEL {
+                    let _ = webview.eval(NAVIGATION_SCRIPT);
+                    let _ = webview.eval(FIND_SCRIPT);
+
+                    if let Some(script) = custom_css_script(webview.app_handle()) {
+                        let _ = webview.eval(&script);
+                    }
+
+                    let spellcheck = webview
+                        .app_handle()
+                        .state::<ConfigState>()
+                        .config
+                        .read()
+                        .unwrap()
+                        .spellcheck;
+                    let _ = webview.eval(&spellcheck_script(spellcheck));
+                }
+
+                // Report page titles so windows can follow them
+                if is_app_window(webview.label()) {
+                    let _ = webview.eval(PAGE_TITLE_SCRIPT);
+                    persist_window_session(webview.app_handle(), None);
+                }
+
+                if let Some(chat_id) = chat_id_from_url(payload.url()) {
+                    record_recent_chat(webview.app_handle(), chat_id);
+                }
+
+                check_server_page_loaded(webview, payload.url());
+            }
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            // Windows still exist here, unlike at `Exit`. `code` is `None` when
+            // the last window closed, which needs no confirmation.
+            RunEvent::ExitRequested { code, api, .. } => {
+                if code.is_none() && *app.state::<QuitState>().recreating_main.lock().unwrap() {
+                    api.prevent_exit();
+                    return;
+                }
+                if code.is_some() && should_confirm_quit(app) {
+                    api.prevent_exit();
+                    confirm_quit(app);
+                    return;
+                }
+
+                // Every way of quitting (tray, menu, last window) ends up here
+                flush_app_state(app);
+            }
+            RunEvent::Exit => {
+                stop_connection_monitor(app);
+                stop_keep_alive(app);
+                stop_auto_reload(app);
+                stop_idle_watcher(app);
+                stop_config_watcher(app);
+            }
+            _ => {}
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_password() -> AppConfig {
+        AppConfig {
+            basic_auth_user: Some("alice".to_string()),
+            basic_auth_pass: Some("s3cret".to_string()),
+            ..AppConfig::default()
+        }
+    }
+
+    #[test]
+    fn serialized_config_leaves_out_secrets() {
+        let value = serde_json::to_value(config_with_password()).unwrap();
+        assert_eq!(value["basic_auth_user"], "alice");
+        assert!(value.get("basic_auth_pass").is_none());
+    }
+
+    #[test]
+    fn secrets_round_trip_through_the_keychain() {
+        let mut stored = config_value_with_secrets(&config_with_password()).unwrap();
+        let keychain: HashMap<&str, Option<String>> =
+            split_secrets(&mut stored).into_iter().collect();
+        assert!(stored.get("basic_auth_pass").is_none());
+        assert_eq!(keychain["basic_auth_pass"].as_deref(), Some("s3cret"));
+
+        let plaintext =
+            recombine_secrets(&mut stored, |field| keychain.get(field).cloned().flatten());
+        assert!(!plaintext);
+        let restored: AppConfig = serde_json::from_value(stored).unwrap();
+        assert_eq!(restored.basic_auth_pass.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn plaintext_secret_wins_over_keychain_and_is_flagged() {
+        let mut value = serde_json::json!({
+            "server_url": "https://onyx.example.com",
+            "basic_auth_pass": "inline",
+        });
+        let plaintext = recombine_secrets(&mut value, |_| Some("keychain".to_string()));
+        assert!(plaintext);
+        assert_eq!(value["basic_auth_pass"], "inline");
+    }
+
+    #[test]
+    fn unset_secret_clears_its_keychain_entry() {
+        let mut stored = config_value_with_secrets(&AppConfig::default()).unwrap();
+        assert_eq!(split_secrets(&mut stored), vec![("basic_auth_pass", None)]);
+    }
+
+    fn managed(value: serde_json::Value) -> serde_json::Map<String, serde_json::Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn managed_settings_win_over_the_users() {
+        let user = AppConfig {
+            always_on_top: true,
+            spellcheck: true,
+            ..AppConfig::default()
+        };
+        let locked = managed(serde_json::json!({ "always_on_top": false }));
+        let effective = apply_managed_config(&user, &locked);
+        assert!(!effective.always_on_top);
+        assert!(effective.spellcheck);
+    }
+
+    #[test]
+    fn managed_server_url_replaces_the_launch_override() {
+        let user = AppConfig {
+            server_url_override: Some("https://cli.example.com".to_string()),
+            ..AppConfig::default()
+        };
+        let unrelated = managed(serde_json::json!({ "spellcheck": false }));
+        let kept = apply_managed_config(&user, &unrelated);
+        assert_eq!(kept.server_url_override, user.server_url_override);
+
+        let server = managed(serde_json::json!({ "server_url": "https://onyx.corp.example" }));
+        let locked = apply_managed_config(&user, &server);
+        assert_eq!(locked.server_url_override, None);
+        assert_eq!(locked.server_url, "https://onyx.corp.example");
+    }
+
+    #[test]
+    fn patches_may_not_change_managed_settings() {
+        let locked = managed(serde_json::json!({ "always_on_top": true }));
+        let change = managed(serde_json::json!({ "always_on_top": false }));
+        assert_eq!(
+            check_managed_patch(&change, &locked),
+            Err(managed_error("always_on_top"))
+        );
+
+        let same = managed(serde_json::json!({ "always_on_top": true, "spellcheck": false }));
+        assert_eq!(check_managed_patch(&same, &locked), Ok(()));
+    }
+
+    #[test]
+    fn managed_settings_are_not_saved() {
+        let locked = managed(serde_json::json!({
+            "always_on_top": true,
+            "window_title": "Corp Onyx",
+        }));
+        let effective = apply_managed_config(&AppConfig::default(), &locked);
+        let mut value = serde_json::to_value(&effective).unwrap();
+        let user = serde_json::json!({ "window_title": "My Onyx" });
+        let defaults = serde_json::to_value(AppConfig::default()).unwrap();
+
+        strip_managed_values(&mut value, &locked, Some(&user), &defaults);
+        assert_eq!(value["window_title"], "My Onyx");
+        assert_eq!(value["always_on_top"], defaults["always_on_top"]);
+    }
+
+    #[test]
+    fn managed_server_url_leaves_saved_profiles_alone() {
+        let user = serde_json::to_value(AppConfig::default()).unwrap();
+        let locked = managed(serde_json::json!({ "server_url": "https://onyx.corp.example" }));
+        let effective = apply_managed_config(&AppConfig::default(), &locked);
+        let mut value = serde_json::to_value(&effective).unwrap();
+
+        strip_managed_values(&mut value, &locked, Some(&user), &user);
+        assert_eq!(value["server_url"], user["server_url"]);
+        assert_eq!(value["servers"], user["servers"]);
+        assert_eq!(value["active_server"], user["active_server"]);
+    }
+
+    #[test]
+    fn relative_navigation_stays_on_the_current_origin() {
+        let current = Url::parse("https://onyx.example.com/chat/abc").unwrap();
+        for path in [
+            "//evil.example/chat",
+            "https://evil.example/",
+            "http://onyx.example.com/chat",
+            "javascript:alert(1)",
+        ] {
+            assert!(resolve_relative_url(&current, path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn tray_tooltip_shows_unread_count_until_read() {
+        let unread = tray_tooltip(ConnectionStatus::Connected, 3);
+        assert_eq!(unread, "Onyx — 3 unread");
+        assert_eq!(tray_tooltip(ConnectionStatus::Connected, 0), "Onyx");
+        assert_eq!(tray_tooltip(ConnectionStatus::Unknown, 0), "Onyx");
+        assert_eq!(tray_tooltip(ConnectionStatus::Offline, 3), "Onyx — Offline");
+    }
+
+    fn profile(name: &str, url: &str) -> ServerProfile {
+        ServerProfile {
+            name: name.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn single_server_url_migrates_to_default_profile() {
+        let v1 = r#"{"version": 1, "server_url": "https://onyx.example.com"}"#;
+        let (config, migrated) = migrate_config(serde_json::from_str(v1).unwrap());
+        assert!(migrated);
+        let expected = profile("Default", "https://onyx.example.com");
+        assert_eq!(config.servers, vec![expected]);
+        assert_eq!(config.active_server, 0);
+    }
+
+    #[test]
+    fn duplicate_profile_names_are_made_unique() {
+        let mut config = AppConfig {
+            server_url: "https://a.example.com".to_string(),
+            servers: vec![
+                profile("Work", "https://a.example.com"),
+                profile("Work", "https://b.example.com"),
+                profile("Work", "https://c.example.com"),
+            ],
+            ..AppConfig::default()
+        };
+        assert!(config.sync_server_profiles());
+        let names: Vec<&str> = config.servers.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Work", "Work (2)", "Work (3)"]);
+        assert!(!config.sync_server_profiles());
+    }
+
+    #[test]
+    fn new_server_gets_a_unique_profile_name() {
+        let mut config = AppConfig {
+            server_url: "https://onyx.example.com".to_string(),
+            servers: vec![profile("onyx.example.com", "https://onyx.example.com")],
+            // No active profile to repoint, so the URL gets a new one
+            active_server: 5,
+            ..AppConfig::default()
+        };
+        config.set_active_server_url("http://onyx.example.com");
+        assert_eq!(config.servers[1].name, "onyx.example.com (2)");
+        assert_eq!(config.active_server, 1);
+    }
+
+    #[test]
+    fn profile_names_must_be_unique() {
+        let mut config = AppConfig::default();
+        let added = config.add_server_profile("Work", "https://a.example.com");
+        assert_eq!(added, Ok(()));
+        let duplicate = config.add_server_profile(" Work ", "https://b.example.com");
+        assert!(duplicate.is_err());
+    }
+
+    fn scratch_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("onyx-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn new_window_falls_back_when_server_url_is_malformed() {
+        let url = new_window_url("https://onyx.example.com", "/chat").unwrap();
+        assert_eq!(url.as_str(), "https://onyx.example.com/chat");
+
+        let fallback = parse_server_url(&default_server_url()).unwrap();
+        for malformed in ["not a url", "localhost:3000", "onyx.example.com"] {
+            assert_eq!(new_window_url(malformed, ""), Some(fallback.clone()));
+        }
+    }
+
+    #[test]
+    fn atomic_write_replaces_a_leftover_temp_file() {
+        let dir = scratch_dir();
+        let path = dir.join(CONFIG_FILE_NAME);
+        write_file_atomically(&path, "{\"version\": 1}").unwrap();
+        // A crash mid-write leaves a truncated temp file behind
+        fs::write(dir.join("config.json.tmp"), "{\"vers").unwrap();
+
+        write_file_atomically(&path, "{\"version\": 2}").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"version\": 2}");
+        assert!(!dir.join("config.json.tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn unparseable_config_is_backed_up() {
+        let dir = scratch_dir();
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&path, "{ not json").unwrap();
+
+        backup_config_file(&path);
+        assert!(!path.exists());
+        let backups: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].starts_with("config.json.bak-"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn versionless_config_migrates_to_current_version() {
+        let old = r#"{"server_url": "https://onyx.example.com"}"#;
+        let (config, migrated) = migrate_config(serde_json::from_str(old).unwrap());
+        assert!(migrated);
+        assert_eq!(config.version, CONFIG_VERSION);
+
+        let (_, migrated_again) = migrate_config(config);
+        assert!(!migrated_again);
+    }
+
+    #[test]
+    fn server_urls_normalize_to_their_origin() {
+        for (input, expected) in [
+            ("https://onyx.example.com/", "https://onyx.example.com"),
+            (
+                "https://onyx.example.com/app?x=1#top",
+                "https://onyx.example.com",
+            ),
+            (
+                "http://onyx.example.com:8080",
+                "http://onyx.example.com:8080",
+            ),
+            ("  https://ONYX.example.com  ", "https://onyx.example.com"),
+        ] {
+            assert_eq!(validate_server_url(input).as_deref(), Ok(expected));
+        }
+        for invalid in ["onyx.example.com", "ftp://onyx.example.com", "https://"] {
+            assert!(validate_server_url(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn shortcut_overrides_fall_back_when_invalid() {
+        let default = Shortcut::new(Some(PRIMARY_MODIFIER), Code::KeyN);
+        let mut overrides = HashMap::new();
+        overrides.insert("new_chat".to_string(), "CmdOrCtrl+T".to_string());
+        let expected = Shortcut::new(Some(PRIMARY_MODIFIER), Code::KeyT);
+        assert_eq!(
+            configured_shortcut(&overrides, "new_chat", default),
+            expected
+        );
+
+        overrides.insert("new_chat".to_string(), "Cmd+Nope".to_string());
+        assert_eq!(
+            configured_shortcut(&overrides, "new_chat", default),
+            default
+        );
+        assert_eq!(configured_shortcut(&overrides, "reload", default), default);
+    }
+
+    #[test]
+    fn hosts_match_ignores_case_and_www() {
+        let server = Url::parse("https://onyx.example.com").unwrap();
+        for same in [
+            "https://onyx.example.com/chat",
+            "http://ONYX.example.com:8080/",
+            "https://www.onyx.example.com/",
+        ] {
+            assert!(hosts_match(&Url::parse(same).unwrap(), &server), "{}", same);
+        }
+        for other in [
+            "https://docs.onyx.app",
+            "https://example.com",
+            "mailto:a@b.c",
+        ] {
+            assert!(
+                !hosts_match(&Url::parse(other).unwrap(), &server),
+                "{}",
+                other
+            );
+        }
+    }
+
+    #[test]
+    fn user_agent_is_optional() {
+        let config: AppConfig =
+            serde_json::from_str(r#"{"server_url": "https://a.example.com"}"#).unwrap();
+        assert_eq!(config.user_agent, None);
+
+        let json = r#"{"server_url": "https://a.example.com", "user_agent": "OnyxDesktop/1.0"}"#;
+        let config: AppConfig = serde_json::from_str(json).unwrap();
+        let state = ConfigState {
+            config: RwLock::new(config),
+            config_initialized: RwLock::new(true),
+        };
+        assert_eq!(state.user_agent().as_deref(), Some("OnyxDesktop/1.0"));
+    }
+
+    #[test]
+    fn hex_colors_parse_with_optional_alpha() {
+        assert_eq!(
+            parse_hex_color("#1a1a2e"),
+            Some(Color(0x1a, 0x1a, 0x2e, 0xff))
+        );
+        assert_eq!(
+            parse_hex_color(" #FFFFFF80 "),
+            Some(Color(0xff, 0xff, 0xff, 0x80))
+        );
+        for malformed in ["1a1a2e", "#1a1a2", "#1a1a2e8", "#gggggg", "#", ""] {
+            assert_eq!(parse_hex_color(malformed), None, "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn build_default_server_url_is_validated() {
+        assert_eq!(resolve_default_server_url(None), ONYX_CLOUD_URL);
+        assert_eq!(resolve_default_server_url(Some("  ")), ONYX_CLOUD_URL);
+        assert_eq!(
+            resolve_default_server_url(Some("not a url")),
+            ONYX_CLOUD_URL
+        );
+        let custom = resolve_default_server_url(Some("https://onyx.corp.example/"));
+        assert_eq!(custom, "https://onyx.corp.example");
+    }
+
+    #[test]
+    fn blank_window_title_falls_back_to_default() {
+        assert_eq!(normalize_window_title("   "), "Onyx");
+        assert_eq!(normalize_window_title(""), "Onyx");
+        assert_eq!(normalize_window_title("  Corp Onyx "), "Corp Onyx");
+    }
+
+    #[test]
+    fn windows_use_the_configured_title() {
+        let mut config = AppConfig {
+            window_title: "Corp Onyx".to_string(),
+            ..AppConfig::default()
+        };
+        assert_eq!(window_title_for_page(&config, Some("Support")), "Corp Onyx");
+
+        config.title_follows_page = true;
+        let title = window_title_for_page(&config, Some("Support"));
+        assert_eq!(title, "Support \u{2014} Corp Onyx");
+        assert_eq!(window_title_for_page(&config, Some("  ")), "Corp Onyx");
+
+        let state = ConfigState {
+            config: RwLock::new(AppConfig {
+                window_title: " ".to_string(),
+                ..AppConfig::default()
+            }),
+            config_initialized: RwLock::new(true),
+        };
+        assert_eq!(state.window_title(), "Onyx");
+    }
+
+    #[test]
+    fn recent_servers_are_deduped_and_capped() {
+        let mut config = AppConfig::default();
+        for n in 0..7 {
+            config.remember_server(&format!("https://{}.example.com", n));
+        }
+        config.remember_server("https://3.example.com");
+        assert_eq!(config.recent_servers.len(), MAX_RECENT_SERVERS);
+        assert_eq!(config.recent_servers[0], "https://3.example.com");
+        let threes = config
+            .recent_servers
+            .iter()
+            .filter(|url| url.contains("//3."))
+            .count();
+        assert_eq!(threes, 1);
+
+        config.server_url = "https://3.example.com".to_string();
+        assert!(!config.recent_servers().contains(&config.server_url));
+    }
+
+    #[test]
+    fn relative_navigation_resolves_like_a_link() {
+        let current = Url::parse("https://onyx.example.com/chat/abc?x=1").unwrap();
+        for (path, expected) in [
+            ("settings", "https://onyx.example.com/chat/settings"),
+            ("../settings", "https://onyx.example.com/settings"),
+            ("/admin", "https://onyx.example.com/admin"),
+            ("?chatId=1", "https://onyx.example.com/chat/abc?chatId=1"),
+        ] {
+            let url = resolve_relative_url(&current, path).unwrap();
+            assert_eq!(url.as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn server_paths_stay_on_the_server() {
+        let server = "https://onyx.example.com";
+        for (path, expected) in [
+            ("/chat", "https://onyx.example.com/chat"),
+            ("", "https://onyx.example.com/"),
+            (".evil.com/x", "https://onyx.example.com/.evil.com/x"),
+            ("@evil.com", "https://onyx.example.com/@evil.com"),
+        ] {
+            let url = server_path_url(server, path).unwrap();
+            assert_eq!(url.as_str(), expected);
+        }
+        for escape in ["//evil.com/x", "https://evil.com", "javascript:alert(1)"] {
+            assert!(server_path_url(server, escape).is_err(), "{}", escape);
+        }
+    }
+
+    #[test]
+    fn exported_config_imports_back() {
+        let mut exported = AppConfig {
+            server_url: "https://onyx.example.com".to_string(),
+            window_title: "Corp Onyx".to_string(),
+            always_on_top: true,
+            ..AppConfig::default()
+        };
+        validate_config(&mut exported).unwrap();
+        let json = serde_json::to_string_pretty(&exported).unwrap();
+
+        let (mut imported, _) = migrate_config(serde_json::from_str(&json).unwrap());
+        validate_config(&mut imported).unwrap();
+        assert_eq!(
+            config_value_with_secrets(&imported),
+            config_value_with_secrets(&exported)
+        );
+    }
+
+    #[test]
+    fn invalid_import_is_rejected() {
+        let json = r#"{"server_url": "onyx.example.com"}"#;
+        let (mut imported, _) = migrate_config(serde_json::from_str(json).unwrap());
+        assert!(validate_config(&mut imported).is_err());
+    }
+
+    #[test]
+    fn fallback_config_dir_follows_the_standard_one() {
+        let exe = Path::new("/opt/onyx/onyx-desktop");
+        let temp = Path::new("/tmp");
+        let standard = PathBuf::from("/home/me/.config/onyx-desktop");
+
+        let candidates = config_dir_candidates(Some(standard.clone()), Some(exe), temp);
+        assert_eq!(candidates[0], standard);
+
+        let candidates = config_dir_candidates(None, Some(exe), temp);
+        assert_eq!(
+            candidates,
+            [
+                Path::new("/opt/onyx").join(FALLBACK_CONFIG_DIR_NAME),
+                temp.join(FALLBACK_CONFIG_DIR_NAME),
+            ]
+        );
+        let candidates = config_dir_candidates(None, None, temp);
+        assert_eq!(candidates, [temp.join(FALLBACK_CONFIG_DIR_NAME)]);
+    }
+
+    #[test]
+    fn chat_ids_cannot_break_out_of_urls_or_scripts() {
+        assert!(is_valid_chat_id("3f2a9c1e-7b4d-4e0a-9c55-0d8e2f6b1a77"));
+        assert!(is_valid_chat_id("chat_42"));
+        for bad in [
+            "",
+            "abc'; alert(1); '",
+            "abc\"",
+            "</script><script>",
+            "abc/../admin",
+            "abc?x=1",
+            "abc def",
+            "é",
+        ] {
+            assert!(!is_valid_chat_id(bad), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn navigation_script_escapes_the_url() {
+        for url in [
+            "https://onyx.example.com/chat?q=it's",
+            "https://onyx.example.com/chat?q=\"quoted\"",
+            "https://onyx.example.com/a\\b",
+            "https://onyx.example.com/chat?q=日本語 ✓",
+            "https://onyx.example.com/';alert(1);'\n",
+        ] {
+            let script = navigation_script(url);
+            let literal = script
+                .strip_prefix("window.location.href = ")
+                .and_then(|rest| rest.strip_suffix(';'))
+                .unwrap();
+            assert_eq!(serde_json::from_str::<String>(literal).unwrap(), url);
+        }
+    }
+
+    #[test]
+    fn config_patches_merge_known_keys_only() {
+        let config = AppConfig::default();
+        let patch = serde_json::json!({ "window_title": "Corp Onyx", "spellcheck": false });
+        let merged = merge_config_patch(&config, &patch).unwrap();
+        assert_eq!(merged.window_title, "Corp Onyx");
+        assert!(!merged.spellcheck);
+        assert_eq!(merged.server_url, config.server_url);
+
+        let unknown = serde_json::json!({ "no_such_setting": true });
+        assert!(merge_config_patch(&config, &unknown).is_err());
+        let wrong_type = serde_json::json!({ "spellcheck": "yes" });
+        assert!(merge_config_patch(&config, &wrong_type).is_err());
+        assert!(merge_config_patch(&config, &serde_json::json!([1])).is_err());
+    }
 }
@@ -0,0 +1,221 @@
+// Find-in-page bar for Onyx Desktop
+// Highlights matches in the page and steps through them with wrap-around
+
+(function () {
+  if (window.__onyxFind) return;
+
+  const BAR_ID = "onyx-desktop-find-bar";
+  const STYLE_ID = "onyx-desktop-find-style";
+  const MATCH_CLASS = "onyx-desktop-find-match";
+  const ACTIVE_CLASS = "onyx-desktop-find-active";
+
+  let matches = [];
+  let activeIndex = -1;
+  let lastQuery = "";
+
+  function injectStyles() {
+    if (document.getElementById(STYLE_ID)) return;
+
+    const style = document.createElement("style");
+    style.id = STYLE_ID;
+    style.textContent = `
+      #${BAR_ID} {
+        position: fixed;
+        top: 44px;
+        right: 16px;
+        z-index: 2147483647;
+        display: none;
+        align-items: center;
+        gap: 6px;
+        padding: 6px 8px;
+        border-radius: 10px;
+        background: rgba(255, 255, 255, 0.96);
+        border: 1px solid rgba(0, 0, 0, 0.1);
+        box-shadow: 0 4px 16px rgba(0, 0, 0, 0.12);
+        font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif;
+        font-size: 13px;
+        color: rgba(0, 0, 0, 0.9);
+      }
+
+      #${BAR_ID}.visible {
+        display: flex;
+      }
+
+      #${BAR_ID} input {
+        width: 180px;
+        padding: 4px 8px;
+        border: 1px solid rgba(0, 0, 0, 0.15);
+        border-radius: 6px;
+        font: inherit;
+        outline: none;
+      }
+
+      #${BAR_ID} .counter {
+        min-width: 48px;
+        text-align: center;
+        color: rgba(0, 0, 0, 0.55);
+      }
+
+      #${BAR_ID} button {
+        padding: 2px 6px;
+        border: none;
+        border-radius: 6px;
+        background: transparent;
+        font: inherit;
+        cursor: pointer;
+      }
+
+      #${BAR_ID} button:hover {
+        background: rgba(0, 0, 0, 0.06);
+      }
+
+      mark.${MATCH_CLASS} {
+        background: #fde68a;
+        color: inherit;
+      }
+
+      mark.${MATCH_CLASS}.${ACTIVE_CLASS} {
+        background: #f59e0b;
+      }
+    `;
+    document.head.appendChild(style);
+  }
+
+  function getBar() {
+    let bar = document.getElementById(BAR_ID);
+    if (bar) return bar;
+
+    injectStyles();
+
+    bar = document.createElement("div");
+    bar.id = BAR_ID;
+    bar.innerHTML = `
+      <input type="text" placeholder="Find in page" spellcheck="false" />
+      <span class="counter"></span>
+      <button type="button" data-action="previous" title="Previous (Shift+Enter)">&#8593;</button>
+      <button type="button" data-action="next" title="Next (Enter)">&#8595;</button>
+      <button type="button" data-action="close" title="Close (Esc)">&#10005;</button>
+    `;
+
+    const input = bar.querySelector("input");
+    input.addEventListener("input", () => find(input.value, true));
+    input.addEventListener("keydown", (e) => {
+      if (e.key === "Enter") {
+        e.preventDefault();
+        find(input.value, !e.shiftKey);
+      } else if (e.key === "Escape") {
+        e.preventDefault();
+        stop();
+      }
+    });
+
+    bar.addEventListener("click", (e) => {
+      const action = e.target.closest("button")?.dataset.action;
+      if (action === "previous") find(input.value, false);
+      else if (action === "next") find(input.value, true);
+      else if (action === "close") stop();
+    });
+
+    document.body.appendChild(bar);
+    return bar;
+  }
+
+  function updateCounter() {
+    const bar = document.getElementById(BAR_ID);
+    if (!bar) return;
+
+    const counter = bar.querySelector(".counter");
+    if (!lastQuery) {
+      counter.textContent = "";
+    } else if (matches.length === 0) {
+      counter.textContent = "0/0";
+    } else {
+      counter.textContent = `${activeIndex + 1}/${matches.length}`;
+    }
+  }
+
+  function clearHighlights() {
+    for (const mark of matches) {
+      const parent = mark.parentNode;
+      if (!parent) continue;
+      parent.replaceChild(document.createTextNode(mark.textContent), mark);
+      parent.normalize();
+    }
+    matches = [];
+    activeIndex = -1;
+  }
+
+  function highlight(query) {
+    clearHighlights();
+    if (!query || !document.body) return;
+
+    const needle = query.toLowerCase();
+    const walker = document.createTreeWalker(document.body, NodeFilter.SHOW_TEXT, {
+      acceptNode(node) {
+        const parent = node.parentElement;
+        if (!parent || parent.closest(`#${BAR_ID}, script, style, noscript, textarea`)) {
+          return NodeFilter.FILTER_REJECT;
+        }
+        return node.nodeValue.toLowerCase().includes(needle)
+          ? NodeFilter.FILTER_ACCEPT
+          : NodeFilter.FILTER_SKIP;
+      },
+    });
+
+    const nodes = [];
+    while (walker.nextNode()) nodes.push(walker.currentNode);
+
+    for (let node of nodes) {
+      let index;
+      while ((index = node.nodeValue.toLowerCase().indexOf(needle)) !== -1) {
+        const match = node.splitText(index);
+        node = match.splitText(needle.length);
+
+        const mark = document.createElement("mark");
+        mark.className = MATCH_CLASS;
+        match.parentNode.replaceChild(mark, match);
+        mark.appendChild(match);
+        matches.push(mark);
+      }
+    }
+  }
+
+  function find(query, forward = true) {
+    // Re-scan when the query changes or the page re-rendered under us
+    if (query !== lastQuery || !matches.every((mark) => mark.isConnected)) {
+      lastQuery = query;
+      highlight(query);
+      activeIndex = forward ? -1 : matches.length;
+    }
+
+    if (matches.length === 0) {
+      updateCounter();
+      return;
+    }
+
+    matches[activeIndex]?.classList.remove(ACTIVE_CLASS);
+    activeIndex = (activeIndex + (forward ? 1 : -1) + matches.length) % matches.length;
+
+    const active = matches[activeIndex];
+    active.classList.add(ACTIVE_CLASS);
+    active.scrollIntoView({ block: "center", behavior: "smooth" });
+    updateCounter();
+  }
+
+  function open() {
+    const bar = getBar();
+    const input = bar.querySelector("input");
+    bar.classList.add("visible");
+    input.focus();
+    input.select();
+  }
+
+  function stop() {
+    clearHighlights();
+    lastQuery = "";
+    updateCounter();
+    document.getElementById(BAR_ID)?.classList.remove("visible");
+  }
+
+  window.__onyxFind = { open, find, stop };
+})();
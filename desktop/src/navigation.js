@@ -0,0 +1,99 @@
+// Back/forward tracking for Onyx Desktop
+// Tags history entries with their position so the app knows whether back and
+// forward are possible, and reports changes to the backend and the titlebar
+
+(function () {
+  if (window.__onyxNavigation) return;
+
+  const INDEX_KEY = "__onyxHistoryIndex";
+  const MAX_KEY = "__onyxHistoryMax";
+  const EVENT_NAME = "onyx-desktop-navigation";
+
+  function getInvoke() {
+    if (window.__TAURI__?.core?.invoke) return window.__TAURI__.core.invoke;
+    if (window.__TAURI__?.invoke) return window.__TAURI__.invoke;
+    if (window.__TAURI_INTERNALS__?.invoke)
+      return window.__TAURI_INTERNALS__.invoke;
+    return null;
+  }
+
+  // sessionStorage is per window and survives reloads and same-origin loads
+  function readMax() {
+    try {
+      const value = Number(sessionStorage.getItem(MAX_KEY));
+      return Number.isFinite(value) ? value : 0;
+    } catch (err) {
+      return 0;
+    }
+  }
+
+  function writeMax(value) {
+    try {
+      sessionStorage.setItem(MAX_KEY, String(value));
+    } catch (err) {}
+  }
+
+  function tagState(state, index) {
+    if (state === null || state === undefined) return { [INDEX_KEY]: index };
+    if (typeof state === "object") return { ...state, [INDEX_KEY]: index };
+    // Primitive states can't carry the tag; leave them alone
+    return state;
+  }
+
+  function stateIndex(state) {
+    const value = state?.[INDEX_KEY];
+    return typeof value === "number" ? value : null;
+  }
+
+  const originalPushState = history.pushState.bind(history);
+  const originalReplaceState = history.replaceState.bind(history);
+
+  let index = stateIndex(history.state);
+  let max = readMax();
+
+  if (index === null) {
+    // A fresh document is the newest entry in the session history
+    index = history.length - 1;
+    max = index;
+    writeMax(max);
+    originalReplaceState(tagState(history.state, index), "");
+  }
+
+  function current() {
+    return { canGoBack: index > 0, canGoForward: index < max };
+  }
+
+  function report() {
+    const state = current();
+    window.dispatchEvent(new CustomEvent(EVENT_NAME, { detail: state }));
+
+    const invoke = getInvoke();
+    if (invoke) {
+      invoke("set_navigation_state", state).catch(() => {});
+    }
+  }
+
+  history.pushState = function (state, title, url) {
+    index += 1;
+    // Pushing drops any entries ahead of the current one
+    max = index;
+    writeMax(max);
+    originalPushState(tagState(state, index), title, url);
+    report();
+  };
+
+  history.replaceState = function (state, title, url) {
+    originalReplaceState(tagState(state, index), title, url);
+  };
+
+  window.addEventListener("popstate", (event) => {
+    const popped = stateIndex(event.state);
+    if (popped !== null) {
+      index = popped;
+      report();
+    }
+  });
+
+  window.__onyxNavigation = { state: current };
+  report();
+})();
@@ -0,0 +1,49 @@
+// Page title tracking for Onyx Desktop
+// Reports the document title to the backend so the window title can follow
+// the current chat. SPA navigation can rename the page several times in a
+// row, so reports are debounced.
+
+(function () {
+  if (window.__onyxPageTitle) return;
+
+  const DEBOUNCE_MS = 250;
+
+  function getInvoke() {
+    if (window.__TAURI__?.core?.invoke) return window.__TAURI__.core.invoke;
+    if (window.__TAURI__?.invoke) return window.__TAURI__.invoke;
+    if (window.__TAURI_INTERNALS__?.invoke)
+      return window.__TAURI_INTERNALS__.invoke;
+    return null;
+  }
+
+  let timer = null;
+  let lastReported = null;
+
+  function report() {
+    timer = null;
+    const title = document.title || "";
+    if (title === lastReported) return;
+    lastReported = title;
+
+    const invoke = getInvoke();
+    if (invoke) {
+      invoke("set_page_title", { title }).catch(() => {});
+    }
+  }
+
+  function schedule() {
+    if (timer !== null) clearTimeout(timer);
+    timer = setTimeout(report, DEBOUNCE_MS);
+  }
+
+  // <title> may be replaced as well as edited, so watch the whole head
+  const observer = new MutationObserver(schedule);
+  observer.observe(document.head || document.documentElement, {
+    childList: true,
+    subtree: true,
+    characterData: true,
+  });
+
+  window.__onyxPageTitle = { report: schedule };
+  report();
+})();
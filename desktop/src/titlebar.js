@@ -2,10 +2,18 @@
 // This script injects a draggable title bar that matches Onyx design system
 
 (function () {
+  // Injected on every page load; only set up once per document
+  if (window.__onyxDesktopTitlebar) return;
+  window.__onyxDesktopTitlebar = true;
+
   const TITLEBAR_ID = "onyx-desktop-titlebar";
   const TITLEBAR_HEIGHT = 36;
   const STYLE_ID = "onyx-desktop-titlebar-style";
   const VIEWPORT_VAR = "--onyx-desktop-viewport-height";
+  const NAV_ID = "onyx-desktop-titlebar-nav";
+  const NAVIGATION_EVENT = "onyx-desktop-navigation";
+  // Clears the macOS traffic lights
+  const NAV_LEFT_OFFSET = 80;
 
   // Wait for DOM to be ready
   if (document.readyState === "loading") {
@@ -104,6 +112,47 @@
         height: var(--onyx-desktop-titlebar-height);
       }
 
+      #${NAV_ID} {
+        position: absolute;
+        left: ${NAV_LEFT_OFFSET}px;
+        top: 0;
+        bottom: 0;
+        display: flex;
+        align-items: center;
+        gap: 2px;
+        -webkit-app-region: no-drag;
+      }
+
+      #${NAV_ID} button {
+        width: 26px;
+        height: 24px;
+        border: none;
+        border-radius: 6px;
+        background: transparent;
+        color: rgba(0, 0, 0, 0.6);
+        font-size: 15px;
+        line-height: 1;
+        cursor: pointer;
+        -webkit-app-region: no-drag;
+      }
+
+      #${NAV_ID} button:hover:not(:disabled) {
+        background: rgba(0, 0, 0, 0.06);
+      }
+
+      #${NAV_ID} button:disabled {
+        opacity: 0.35;
+        cursor: default;
+      }
+
+      .dark #${NAV_ID} button {
+        color: rgba(255, 255, 255, 0.7);
+      }
+
+      .dark #${NAV_ID} button:hover:not(:disabled) {
+        background: rgba(255, 255, 255, 0.08);
+      }
+
       /* Dark mode support */
       .dark #${TITLEBAR_ID} {
         background: linear-gradient(180deg, rgba(18, 18, 18, 0.82) 0%, rgba(18, 18, 18, 0.72) 100%);
@@ -158,9 +207,44 @@
       padding: 0 12px;
     `;
 
+    titleBar.appendChild(buildNavControls());
     return titleBar;
   }
 
+  function buildNavControls() {
+    const nav = document.createElement("div");
+    nav.id = NAV_ID;
+
+    const buttons = [
+      ["back", "\u2039", "Back", "go_back"],
+      ["forward", "\u203A", "Forward", "go_forward"],
+      ["home", "\u2302", "Home", "go_home"],
+    ];
+    for (const [name, label, title, command] of buttons) {
+      const button = document.createElement("button");
+      button.type = "button";
+      button.dataset.nav = name;
+      button.textContent = label;
+      button.title = title;
+      button.addEventListener("click", () => {
+        const invoke = getInvoke();
+        if (invoke) invoke(command).catch(() => {});
+      });
+      nav.appendChild(button);
+    }
+
+    updateNavControls(nav, window.__onyxNavigation?.state());
+    return nav;
+  }
+
+  function updateNavControls(nav, state) {
+    if (!nav) return;
+    const back = nav.querySelector('[data-nav="back"]');
+    const forward = nav.querySelector('[data-nav="forward"]');
+    if (back) back.disabled = !state?.canGoBack;
+    if (forward) forward.disabled = !state?.canGoForward;
+  }
+
   function mountTitleBar() {
     if (!document.body) {
       return;
@@ -196,6 +280,9 @@
 
   function init() {
     mountTitleBar();
+    window.addEventListener(NAVIGATION_EVENT, (event) => {
+      updateNavControls(document.getElementById(NAV_ID), event.detail);
+    });
     syncViewportHeight();
     window.addEventListener("resize", syncViewportHeight, { passive: true });
     window.visualViewport?.addEventListener("resize", syncViewportHeight, {